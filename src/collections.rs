@@ -0,0 +1,9 @@
+//! Map and set types used by the solvers. With the `std` feature these are
+//! the hash based std collections, without it the B-tree ones from `alloc`.
+
+pub use alloc::collections::VecDeque;
+
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
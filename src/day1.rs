@@ -1,53 +1,354 @@
-fn shift<T: Copy>(vec: &Vec<T>, distance: usize) -> Vec<T> {
-    let mut new_vector = Vec::with_capacity(vec.len());
+use prelude::*;
 
-    for i in distance..vec.len() + distance {
-        if i >= vec.len() {
-            new_vector.push(vec[i % vec.len()]);
+/// How the symbols of a captcha are split and valued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Single character digits in the given radix, between 2 and 36.
+    Radix(u32),
+    /// Single letters valued by their position in the alphabet, `a` and `A`
+    /// are both 1.
+    Letters,
+    /// Decimal numbers separated by the given delimiter.
+    Tokens(char),
+}
+
+impl Alphabet {
+    fn value(&self, symbol: &str) -> Option<u64> {
+        match *self {
+            Alphabet::Radix(radix) => single(symbol)
+                .and_then(|c| c.to_digit(radix))
+                .map(u64::from),
+            Alphabet::Letters => single(symbol)
+                .filter(|c| c.is_ascii_alphabetic())
+                .map(|c| u64::from(c.to_ascii_lowercase() as u8 - b'a' + 1)),
+            Alphabet::Tokens(_) => symbol.trim().parse::<u64>().ok(),
+        }
+    }
+}
+
+fn single(symbol: &str) -> Option<char> {
+    let mut chars = symbol.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Checks that every byte is an ASCII digit, errors give the byte offset.
+fn validate(digits: &[u8]) -> Result<(), String> {
+    match digits.iter().position(|byte| !byte.is_ascii_digit()) {
+        Some(offset) => Err(format!(
+            "Invalid byte {:?} at byte offset {}",
+            digits[offset] as char, offset
+        )),
+        None => Ok(()),
+    }
+}
+
+fn digit(byte: u8) -> u64 {
+    u64::from(byte - b'0')
+}
+
+fn matching_sum<T, F>(symbols: &[T], distance: usize, value: F) -> u64
+where
+    T: PartialEq,
+    F: Fn(&T) -> u64,
+{
+    let len = symbols.len();
+    if len == 0 {
+        return 0;
+    }
+    let distance = distance % len;
+
+    symbols.iter().enumerate().fold(0, |acc, (i, x)| {
+        if *x == symbols[(i + distance) % len] {
+            acc + value(x)
         } else {
-            new_vector.push(vec[i]);
+            acc
+        }
+    })
+}
+
+/// Sums the digits that match the digit `distance` steps further along the
+/// circular sequence, without allocating. Fails on anything but ASCII
+/// digits.
+pub fn captcha(digits: &[u8], distance: usize) -> Result<u64, String> {
+    validate(digits)?;
+
+    Ok(matching_sum(digits, distance, |&x| digit(x)))
+}
+
+/// Prime modulus of the number theoretic transform, `15 * 2^27 + 1`.
+const MODULUS: u64 = 2_013_265_921;
+/// Generates the multiplicative group modulo `MODULUS`.
+const ROOT: u64 = 31;
+/// The largest transform `MODULUS` supports.
+const MAX_TRANSFORM: usize = 1 << 27;
+
+fn pow_mod(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % MODULUS;
         }
+        base = base * base % MODULUS;
+        exponent >>= 1;
     }
 
-    new_vector
+    result
 }
 
-pub fn solve(input: &str, distance: usize) -> u32 {
-    let parsed = input
-        .trim()
+/// In place number theoretic transform of `values`, whose length must be a
+/// power of two no larger than `MAX_TRANSFORM`.
+fn transform(values: &mut [u64], invert: bool) {
+    let len = values.len();
+
+    let mut j = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut width = 2;
+    while width <= len {
+        let mut step = pow_mod(ROOT, (MODULUS - 1) / width as u64);
+        if invert {
+            step = pow_mod(step, MODULUS - 2);
+        }
+        for chunk in values.chunks_mut(width) {
+            let (low, high) = chunk.split_at_mut(width / 2);
+            let mut twiddle = 1;
+            for (a, b) in low.iter_mut().zip(high.iter_mut()) {
+                let t = *b * twiddle % MODULUS;
+                *b = (*a + MODULUS - t) % MODULUS;
+                *a = (*a + t) % MODULUS;
+                twiddle = twiddle * step % MODULUS;
+            }
+        }
+        width <<= 1;
+    }
+
+    if invert {
+        let scale = pow_mod(len as u64, MODULUS - 2);
+        for value in values.iter_mut() {
+            *value = *value * scale % MODULUS;
+        }
+    }
+}
+
+/// The captcha sum for every distance, indexed by distance. Instead of one
+/// pass per distance this takes the circular autocorrelation of every
+/// digit's indicator sequence, which counts the pairs of its occurrences at
+/// each distance, through number theoretic transforms in O(n log n). Fails
+/// on anything but ASCII digits and on more than 2^26 digits, past which
+/// the transform no longer fits the modulus.
+pub fn all_distances(digits: &[u8]) -> Result<Vec<u64>, String> {
+    validate(digits)?;
+    let len = digits.len();
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let size = (2 * len).next_power_of_two();
+    if size > MAX_TRANSFORM {
+        return Err(format!("Too many digits ({}) for all distances", len));
+    }
+
+    // Every digit contributes `value * forward * backward`, the transform
+    // of the linear correlation of its indicator with itself.
+    let mut spectrum = vec![0; size];
+    for value in 1..10 {
+        let mut forward = vec![0; size];
+        let mut backward = vec![0; size];
+        for (i, &x) in digits.iter().enumerate() {
+            if digit(x) == value {
+                forward[i] = 1;
+                backward[len - 1 - i] = 1;
+            }
+        }
+        if forward.iter().all(|&bit| bit == 0) {
+            continue;
+        }
+
+        transform(&mut forward, false);
+        transform(&mut backward, false);
+        for ((total, a), b) in spectrum.iter_mut().zip(forward).zip(backward) {
+            *total = (*total + value * (a * b % MODULUS)) % MODULUS;
+        }
+    }
+    transform(&mut spectrum, true);
+
+    // Index `len - 1 - distance` holds the pairs `distance` apart without
+    // wrapping around, `2 * len - 1 - distance` those that wrap.
+    Ok((0..len)
+        .map(|distance| spectrum[len - 1 - distance] + spectrum[2 * len - 1 - distance])
+        .collect())
+}
+
+/// Errors give the 0-based position of the offending symbol in the trimmed
+/// input, counted in tokens for `Alphabet::Tokens` and in characters (not
+/// bytes) otherwise.
+pub fn parse(input: &str, alphabet: Alphabet) -> Result<Vec<u64>, String> {
+    if let Alphabet::Radix(radix) = alphabet {
+        if !(2..=36).contains(&radix) {
+            return Err(format!("Unsupported radix {}", radix));
+        }
+    }
+
+    let input = input.trim();
+    let unit = match alphabet {
+        Alphabet::Tokens(_) => "token",
+        _ => "character",
+    };
+    let symbols: Vec<&str> = match alphabet {
+        Alphabet::Tokens(_) if input.is_empty() => Vec::new(),
+        Alphabet::Tokens(delimiter) => input.split(delimiter).collect(),
+        _ => input
+            .char_indices()
+            .map(|(i, c)| &input[i..i + c.len_utf8()])
+            .collect(),
+    };
+
+    symbols
+        .iter()
+        .enumerate()
+        .map(|(position, symbol)| {
+            alphabet
+                .value(symbol)
+                .ok_or_else(|| format!("Invalid symbol {:?} at {} {}", symbol, unit, position))
+        })
+        .collect()
+}
+
+pub fn solve_with(input: &str, alphabet: Alphabet, distance: usize) -> Result<u64, String> {
+    let values = parse(input, alphabet)?;
+
+    Ok(matching_sum(&values, distance, |&x| x))
+}
+
+/// Errors give the 0-based character (not byte) position of the offending
+/// symbol in the trimmed input, like `parse`.
+pub fn solve(input: &str, distance: usize) -> Result<u64, String> {
+    let digits = input.trim();
+    if let Some((position, symbol)) = digits
         .chars()
-        .map(|v| v.to_digit(10).expect("Expected digits"))
-        .collect::<Vec<u32>>();
-    let shifted = shift(&parsed, distance);
-    let zipped = parsed.iter().zip(shifted.iter());
+        .enumerate()
+        .find(|&(_, c)| !c.is_ascii_digit())
+    {
+        return Err(format!(
+            "Invalid symbol {:?} at character {}",
+            symbol, position
+        ));
+    }
 
-    zipped.fold(0, |acc, (x, y)| if x == y { acc + x } else { acc })
+    captcha(digits.as_bytes(), distance)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{shift, solve};
+    use super::{all_distances, captcha, parse, solve, solve_with, Alphabet};
+    use prelude::*;
 
     #[test]
     fn test_cases_star_one() {
-        assert_eq!(solve("1122", 1), 3);
-        assert_eq!(solve("1111", 1), 4);
-        assert_eq!(solve("1234", 1), 0);
-        assert_eq!(solve("91212129", 1), 9);
+        assert_eq!(solve("1122", 1), Ok(3));
+        assert_eq!(solve("1111", 1), Ok(4));
+        assert_eq!(solve("1234", 1), Ok(0));
+        assert_eq!(solve("91212129", 1), Ok(9));
     }
 
     #[test]
     fn test_cases_star_two() {
-        assert_eq!(solve("1212", 2), 6);
-        assert_eq!(solve("1221", 2), 0);
-        assert_eq!(solve("123425", 3), 4);
-        assert_eq!(solve("123123", 3), 12);
-        assert_eq!(solve("12131415", 4), 4);
+        assert_eq!(solve("1212", 2), Ok(6));
+        assert_eq!(solve("1221", 2), Ok(0));
+        assert_eq!(solve("123425", 3), Ok(4));
+        assert_eq!(solve("123123", 3), Ok(12));
+        assert_eq!(solve("12131415", 4), Ok(4));
+    }
+
+    #[test]
+    fn test_large_distance() {
+        assert_eq!(solve("1122", usize::MAX), solve("1122", 3));
+        assert_eq!(captcha(b"1122", usize::MAX), Ok(3));
+        assert_eq!(solve("", usize::MAX), Ok(0));
+    }
+
+    #[test]
+    fn test_all_distances() {
+        let digits = b"912121293";
+        let sums = all_distances(digits).unwrap();
+
+        assert_eq!(sums.len(), digits.len());
+        for (distance, &sum) in sums.iter().enumerate() {
+            assert_eq!(Ok(sum), captcha(digits, distance));
+        }
+        assert_eq!(all_distances(b"1212"), Ok(vec![6, 0, 6, 0]));
+        assert_eq!(
+            all_distances(b"12\xff"),
+            Err("Invalid byte '\u{ff}' at byte offset 2".to_owned())
+        );
+        assert!(captcha(b"1 2", 1).is_err());
+        assert_eq!(all_distances(b""), Ok(vec![]));
+        assert_eq!(all_distances(b"7"), Ok(vec![7]));
+    }
+
+    #[test]
+    fn test_all_distances_large() {
+        let mut seed = 2017u64;
+        let digits = (0..3000)
+            .map(|_| {
+                seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                b'0' + ((seed >> 33) % 10) as u8
+            })
+            .collect::<Vec<_>>();
+        let sums = all_distances(&digits).unwrap();
+
+        for distance in (0..digits.len()).step_by(7) {
+            assert_eq!(Ok(sums[distance]), captcha(&digits, distance));
+        }
+        assert_eq!(all_distances(&[b'9'; 1000]).unwrap(), vec![9000; 1000]);
+    }
+
+    #[test]
+    fn test_alphabets() {
+        assert_eq!(solve_with("1122", Alphabet::Radix(10), 1), Ok(3));
+        assert_eq!(solve_with("ff0aA", Alphabet::Radix(16), 1), Ok(25));
+        assert_eq!(solve_with("abBzz", Alphabet::Letters, 1), Ok(28));
+        assert_eq!(solve_with("12,7,12,7", Alphabet::Tokens(','), 2), Ok(38));
+        assert_eq!(solve_with("", Alphabet::Tokens(','), 1), Ok(0));
     }
 
     #[test]
-    fn test_shift() {
-        let vec = vec![1, 2, 3, 4];
-        assert_eq!(shift(&vec, 1), vec![2, 3, 4, 1]);
+    fn test_invalid_symbols() {
+        assert_eq!(
+            solve("12a4", 1),
+            Err("Invalid symbol 'a' at character 2".to_owned())
+        );
+        assert_eq!(
+            solve("1é3x", 1),
+            Err("Invalid symbol 'é' at character 1".to_owned())
+        );
+        assert_eq!(
+            parse("12g", Alphabet::Radix(16)),
+            Err("Invalid symbol \"g\" at character 2".to_owned())
+        );
+        assert_eq!(
+            parse("ab1", Alphabet::Letters),
+            Err("Invalid symbol \"1\" at character 2".to_owned())
+        );
+        assert_eq!(
+            parse("1;x;3", Alphabet::Tokens(';')),
+            Err("Invalid symbol \"x\" at token 1".to_owned())
+        );
+        assert!(parse("é", Alphabet::Letters).is_err());
+        assert!(parse("1", Alphabet::Radix(37)).is_err());
     }
 }
@@ -1,3 +1,5 @@
+use prelude::*;
+
 #[derive(Debug)]
 struct List {
     storage: Vec<u8>,
@@ -78,12 +80,23 @@ pub fn knot_hash(input: Vec<usize>, size: usize, rounds: usize) -> String {
     list.dense_hash()
 }
 
+pub fn parse(input: &str, size: usize) -> Result<Vec<usize>, String> {
+    input
+        .trim()
+        .split(',')
+        .map(|v| match v.trim().parse::<usize>() {
+            Ok(length) if length <= size => Ok(length),
+            Ok(length) => Err(format!(
+                "Length {} must not be larger than the list size {}",
+                length, size
+            )),
+            Err(_) => Err(format!("Expected only valid numbers found {}", v)),
+        })
+        .collect()
+}
+
 pub fn solve(input: &str, size: usize) -> u16 {
-    let lengths = input.trim().split(',').map(|v| {
-        v.trim()
-            .parse::<usize>()
-            .expect(&format!("Expected only valid numbers found {}", v))
-    });
+    let lengths = parse(input, size).expect("Invalid lengths");
     let mut list = List::new(size);
 
     for length in lengths {
@@ -113,6 +126,13 @@ mod tests {
         assert_eq!(solve("3, 4, 1, 5", 5), 12);
     }
 
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("3,4,x", 5).is_err());
+        assert!(parse("3,6", 5).is_err());
+        assert!(parse("", 5).is_err());
+    }
+
     #[test]
     fn test_cases_star_two() {
         assert_eq!(solve2("1,2,3", 256, 64), "3efbe78a8d82f29979031a4aa0b16a9d");
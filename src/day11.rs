@@ -1,5 +1,7 @@
+use prelude::*;
+
 #[derive(Debug)]
-enum Direction {
+pub enum Direction {
     N,
     NE,
     NW,
@@ -58,12 +60,16 @@ impl Location {
     }
 }
 
-pub fn solve(input: &str) -> i32 {
-    let steps = input
+pub fn parse(input: &str) -> Result<Vec<Direction>, String> {
+    input
         .trim()
         .split(',')
-        .map(|s| Direction::parse(s.trim()).expect(&format!("Unparsable direction {}", s)))
-        .collect::<Vec<_>>();
+        .map(|s| Direction::parse(s.trim()).ok_or_else(|| format!("Unparsable direction {}", s)))
+        .collect()
+}
+
+pub fn solve(input: &str) -> i32 {
+    let steps = parse(input).expect("Invalid path");
 
     let loc = steps
         .iter()
@@ -73,11 +79,7 @@ pub fn solve(input: &str) -> i32 {
 }
 
 pub fn solve2(input: &str) -> i32 {
-    let steps = input
-        .trim()
-        .split(',')
-        .map(|s| Direction::parse(s.trim()).expect(&format!("Unparsable direction {}", s)))
-        .collect::<Vec<_>>();
+    let steps = parse(input).expect("Invalid path");
 
     let origin = Location::origin();
     steps
@@ -106,6 +108,13 @@ mod tests {
         assert_eq!(solve("se,sw,se,sw,sw"), 3);
     }
 
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("ne,,n").is_err());
+        assert!(parse("ne,up").is_err());
+    }
+
     #[test]
     fn test_mv() {
         assert_eq!(
@@ -1,9 +1,10 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use collections::{Map, Set, VecDeque};
+use prelude::*;
 
-fn groups(connections: &HashMap<u32, Vec<u32>>) -> HashSet<Vec<u32>> {
-    let mut result = HashSet::<Vec<u32>>::new();
+fn groups(connections: &Map<u32, Vec<u32>>) -> Set<Vec<u32>> {
+    let mut result = Set::<Vec<u32>>::new();
     for (key, _) in connections {
-        let mut programs = HashSet::<u32>::new();
+        let mut programs = Set::<u32>::new();
         let mut queue = VecDeque::<u32>::new();
         queue.push_back(*key);
 
@@ -28,25 +29,35 @@ fn groups(connections: &HashMap<u32, Vec<u32>>) -> HashSet<Vec<u32>> {
     result
 }
 
-pub fn solve(input: &str) -> u32 {
-    let connections: HashMap<u32, Vec<u32>> = input
-        .trim()
-        .lines()
-        .map(|line| {
-            let words = line.split_whitespace().collect::<Vec<_>>();
-            let id = words[0].parse::<u32>().expect("Expected number");
-            let connections = words[2..]
-                .iter()
-                .map(|v| {
-                    v.trim_matches(|c: char| !c.is_numeric())
-                        .parse::<u32>()
-                        .expect("Expected number")
-                }).collect::<Vec<_>>();
+fn parse_line(line: &str) -> Result<(u32, Vec<u32>), String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if words.len() < 3 || words[1] != "<->" {
+        return Err(format!("Expected `<id> <-> <ids>` found {:?}", line));
+    }
+
+    let id = words[0]
+        .parse::<u32>()
+        .map_err(|_| format!("Expected number found {:?}", words[0]))?;
+    let connections = words[2..]
+        .iter()
+        .map(|v| {
+            v.trim_matches(',')
+                .parse::<u32>()
+                .map_err(|_| format!("Expected number found {:?}", v))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((id, connections))
+}
+
+pub fn parse(input: &str) -> Result<Map<u32, Vec<u32>>, String> {
+    input.trim().lines().map(parse_line).collect()
+}
 
-            (id, connections)
-        }).collect();
+pub fn solve(input: &str) -> u32 {
+    let connections = parse(input).expect("Invalid input");
 
-    let mut programs = HashSet::<u32>::new();
+    let mut programs = Set::<u32>::new();
     let mut queue = VecDeque::<u32>::new();
     queue.push_back(0);
 
@@ -68,29 +79,14 @@ pub fn solve(input: &str) -> u32 {
 }
 
 pub fn solve2(input: &str) -> u32 {
-    let connections: HashMap<u32, Vec<u32>> = input
-        .trim()
-        .lines()
-        .map(|line| {
-            let words = line.split_whitespace().collect::<Vec<_>>();
-            let id = words[0].parse::<u32>().expect("Expected number");
-            let connections = words[2..]
-                .iter()
-                .map(|v| {
-                    v.trim_matches(|c: char| !c.is_numeric())
-                        .parse::<u32>()
-                        .expect("Expected number")
-                }).collect::<Vec<_>>();
-
-            (id, connections)
-        }).collect();
+    let connections = parse(input).expect("Invalid input");
 
     groups(&connections).len() as u32
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{solve, solve2};
+    use super::{parse, solve, solve2};
 
     #[test]
     fn test_cases_star_one() {
@@ -119,4 +115,12 @@ mod tests {
                 ";
         assert_eq!(solve2(&input), 2);
     }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("0").is_err());
+        assert!(parse("0 <->").is_err());
+        assert!(parse("0 -> 1").is_err());
+        assert!(parse("0 <-> 1, x").is_err());
+    }
 }
@@ -1,42 +1,58 @@
-use std::collections::HashMap;
-use std::ops::Range;
+use collections::Map;
+use prelude::*;
 
 #[derive(Debug)]
-struct State {
-    levels: HashMap<u32, u32>,
-    scanner_locations: HashMap<u32, (u32, i32)>,
+pub struct State {
+    levels: Map<u32, u32>,
+    scanner_locations: Map<u32, (u32, i32)>,
     packect_location: i32,
     severity: u32,
     final_location: u32,
 }
 
 impl State {
-    fn parse(input: &str) -> Self {
+    fn parse_line(line: &str) -> Result<(u32, u32), String> {
+        let parts = line.trim().split(':').collect::<Vec<_>>();
+        if parts.len() != 2 {
+            return Err(format!("Expected `<depth>: <range>` found {:?}", line));
+        }
+
+        let depth = parts[0]
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid depth in {:?}", line))?;
+        let range = parts[1]
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid range in {:?}", line))?;
+        if range < 2 {
+            return Err(format!("Scanner range must be at least 2 in {:?}", line));
+        }
+
+        Ok((depth, range))
+    }
+
+    fn parse(input: &str) -> Result<Self, String> {
         let levels = input
             .trim()
             .lines()
-            .map(|line| {
-                let parts = line.trim().split(':').collect::<Vec<_>>();
-                (
-                    parts[0].parse::<u32>().unwrap(),
-                    parts[1].trim().parse::<u32>().unwrap(),
-                )
-            }).collect::<HashMap<_, _>>();
+            .map(Self::parse_line)
+            .collect::<Result<Map<_, _>, _>>()?;
 
         let scanner_locations = levels
             .keys()
             .map(|level| (*level, (0, 1)))
-            .collect::<HashMap<_, _>>();
+            .collect::<Map<_, _>>();
 
         let final_location: u32 = *levels.keys().max().unwrap_or(&0);
 
-        State {
+        Ok(State {
             levels,
             scanner_locations,
             packect_location: -1,
             severity: 0,
             final_location,
-        }
+        })
     }
 
     fn advance(&mut self) {
@@ -99,8 +115,12 @@ impl Iterator for Counter {
     }
 }
 
+pub fn parse(input: &str) -> Result<State, String> {
+    State::parse(input)
+}
+
 pub fn solve(input: &str) -> u32 {
-    let mut state = State::parse(input);
+    let mut state = State::parse(input).expect("Invalid firewall");
 
     while !state.at_end() {
         state.advance();
@@ -110,7 +130,7 @@ pub fn solve(input: &str) -> u32 {
 }
 
 pub fn solve2(input: &str) -> u32 {
-    let mut state = State::parse(input);
+    let state = State::parse(input).expect("Invalid firewall");
     let mut severity = 1;
     let mut delay = 10;
 
@@ -121,7 +141,23 @@ pub fn solve2(input: &str) -> u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{solve, solve2};
+    #[cfg(feature = "std")]
+    use super::State;
+    use super::{parse, solve, solve2};
+    #[cfg(feature = "std")]
+    use snapshot::assert_snapshot;
+
+    #[cfg(feature = "std")]
+    fn scanner_positions(state: &State) -> String {
+        let mut levels = state.scanner_locations.keys().collect::<Vec<_>>();
+        levels.sort();
+
+        levels
+            .iter()
+            .map(|level| format!("{}@{}", level, state.scanner_locations[level].0))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 
     #[test]
     fn test_cases_star_one() {
@@ -134,6 +170,34 @@ mod tests {
         assert_eq!(solve(&input), 24);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_scanner_snapshot() {
+        let mut state = parse(
+            "
+                0: 3
+                1: 2
+                4: 4
+                6: 4
+                ",
+        )
+        .unwrap();
+        let mut picoseconds = Vec::new();
+
+        while !state.at_end() {
+            state.advance();
+            picoseconds.push(format!(
+                "{}: packet {} severity {} scanners {}",
+                picoseconds.len(),
+                state.packect_location,
+                state.severity,
+                scanner_positions(&state)
+            ));
+        }
+
+        assert_snapshot("day13_example_scanners", &picoseconds.join("\n"));
+    }
+
     #[test]
     fn test_cases_star_two() {
         let input = "
@@ -144,4 +208,12 @@ mod tests {
                 ";
         assert_eq!(solve2(&input), 10);
     }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("0").is_err());
+        assert!(parse("0: 3: 1").is_err());
+        assert!(parse("a: 3").is_err());
+        assert!(parse("0: 1").is_err());
+    }
 }
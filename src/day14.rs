@@ -1,6 +1,6 @@
-use std::collections::{HashMap, VecDeque};
-
+use collections::{Map, VecDeque};
 use day10::knot_hash;
+use prelude::*;
 
 fn str_as_ascii_values(input: &str) -> Vec<usize> {
     input
@@ -48,7 +48,8 @@ pub fn solve2(input: &str) -> u32 {
                 let is_empty = ((v >> y) & (0b1 as u128)) as u32 == 0;
                 ((x, 127 - y), is_empty)
             })
-        }).collect::<HashMap<_, _>>();
+        })
+        .collect::<Map<_, _>>();
     let mut num_regions = 0;
     let neighbours: &[(i32, i32)] = &[(0, 1), (0, -1), (1, 0), (-1, 0)];
 
@@ -84,9 +85,25 @@ pub fn solve2(input: &str) -> u32 {
     num_regions
 }
 
+pub fn visualize(input: &str) -> String {
+    build(input)
+        .iter()
+        .map(|row| {
+            (0..128)
+                .rev()
+                .map(|i| if (row >> i) & 1 == 1 { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{solve, solve2};
+    use super::{solve, solve2, visualize};
+    use prelude::*;
+    #[cfg(feature = "std")]
+    use snapshot::assert_snapshot;
 
     #[test]
     fn test_cases_star_one() {
@@ -94,4 +111,22 @@ mod tests {
         assert_eq!(solve(&input), 8108);
         assert_eq!(solve2(&input), 1242);
     }
+
+    #[test]
+    fn test_visualize() {
+        let grid = visualize("flqrgnkx");
+        let rows = grid
+            .lines()
+            .take(3)
+            .map(|row| &row[..8])
+            .collect::<Vec<_>>();
+
+        assert_eq!(rows, ["##.#.#..", ".#.#.#.#", "....#.#."]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_grid_snapshot() {
+        assert_snapshot("day14_example_grid", &visualize("flqrgnkx"));
+    }
 }
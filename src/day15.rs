@@ -70,6 +70,7 @@ pub fn solve2(initial_value_a: i64, initial_value_b: i64) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use prelude::*;
 
     #[test]
     fn test_first_five() {
@@ -1,5 +1,5 @@
-use std::collections::HashSet;
-use std::fmt;
+use core::fmt;
+use prelude::*;
 
 struct Programs {
     programs: Vec<char>,
@@ -51,42 +51,51 @@ impl fmt::Display for Programs {
     }
 }
 
-enum Action {
+pub enum Action {
     Spin(usize),
     Exchange(usize, usize),
     Partner(char, char),
 }
 
+fn split_pair(input: &str) -> Result<(&str, &str), String> {
+    let rest = input.split('/').collect::<Vec<_>>();
+    if rest.len() != 2 {
+        return Err(format!(
+            "Expected two arguments separated by `/` in {}",
+            input
+        ));
+    }
+
+    Ok((rest[0].trim(), rest[1].trim()))
+}
+
 impl Action {
-    fn parse(input: &str) -> Option<Action> {
+    fn parse(input: &str) -> Result<Action, String> {
         let clean: String = input.trim().to_lowercase();
-        match &clean[0..1] {
-            "s" => {
-                let offset = input[1..].parse::<usize>();
-
-                offset.map(|v| Some(Action::Spin(v))).unwrap_or(None)
+        let mut chars = clean.chars();
+        let kind = chars.next();
+        let rest = chars.as_str();
+
+        match kind {
+            Some('s') => rest
+                .parse::<usize>()
+                .map(Action::Spin)
+                .map_err(|_| format!("Invalid spin offset in {}", input)),
+            Some('x') => {
+                let (p1, p2) = split_pair(rest)?;
+                match (p1.parse::<usize>(), p2.parse::<usize>()) {
+                    (Ok(p1), Ok(p2)) => Ok(Action::Exchange(p1, p2)),
+                    _ => Err(format!("Expected numeric exchange arguments in {}", input)),
+                }
             }
-            "x" => {
-                let rest = input[1..].split("/").collect::<Vec<_>>();
-                let p1 = rest[0]
-                    .trim()
-                    .parse::<usize>()
-                    .expect("Expected numeric exchange argument");
-                let p2 = rest[1]
-                    .trim()
-                    .parse::<usize>()
-                    .expect("Expected numeric exchange argument");
-
-                Some(Action::Exchange(p1, p2))
+            Some('p') => {
+                let (p1, p2) = split_pair(rest)?;
+                match (p1.chars().next(), p2.chars().next()) {
+                    (Some(p1), Some(p2)) => Ok(Action::Partner(p1, p2)),
+                    _ => Err(format!("Expected two partner names in {}", input)),
+                }
             }
-            "p" => {
-                let rest = input[1..].split("/").collect::<Vec<_>>();
-                let p1 = rest[0].chars().take(1).collect::<Vec<_>>()[0];
-                let p2 = rest[1].chars().take(1).collect::<Vec<_>>()[0];
-
-                Some(Action::Partner(p1, p2))
-            }
-            _ => None,
+            _ => Err(format!("Invalid action {}", input)),
         }
     }
 }
@@ -101,13 +110,43 @@ impl fmt::Display for Action {
     }
 }
 
-pub fn solve(input: &str, num_programs: u32, repeats: u32) -> String {
+pub fn parse(input: &str) -> Result<Vec<Action>, String> {
+    input.trim().split(',').map(Action::parse).collect()
+}
+
+/// Checks that every action only refers to positions and programs that exist
+/// in a line-up of `num_programs`.
+pub fn validate(actions: &[Action], num_programs: u32) -> Result<(), String> {
+    if num_programs > 26 {
+        return Err(format!(
+            "Cannot handle {} programs, at most 26",
+            num_programs
+        ));
+    }
+
+    let len = num_programs as usize;
+    let known = |name: char| name >= 'a' && (name as u32) < 'a' as u32 + num_programs;
+    for action in actions {
+        let valid = match *action {
+            Action::Spin(offset) => offset <= len,
+            Action::Exchange(i1, i2) => i1 < len && i2 < len,
+            Action::Partner(a, b) => known(a) && known(b),
+        };
+        if !valid {
+            return Err(format!(
+                "Action {} does not fit {} programs",
+                action, num_programs
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn solve(input: &str, num_programs: u32, repeats: u32) -> Result<String, String> {
+    let actions = parse(input)?;
+    validate(&actions, num_programs)?;
     let mut programs = Programs::new(num_programs);
-    let actions = input
-        .trim()
-        .split(',')
-        .map(|value| Action::parse(value).expect("Invalid action"))
-        .collect::<Vec<_>>();
 
     for i in (0..repeats) {
         for action in actions.iter() {
@@ -115,15 +154,101 @@ pub fn solve(input: &str, num_programs: u32, repeats: u32) -> String {
         }
     }
 
-    programs.to_string()
+    Ok(programs.to_string())
+}
+
+/// The number of rounds of the dance after which the programs are back in
+/// their initial order.
+pub fn cycle_length(input: &str, num_programs: u32) -> Result<u32, String> {
+    let actions = parse(input)?;
+    validate(&actions, num_programs)?;
+    let initial = Programs::new(num_programs).programs;
+    let mut programs = Programs::new(num_programs);
+    let mut rounds = 0;
+
+    loop {
+        for action in actions.iter() {
+            programs.apply(action);
+        }
+        rounds += 1;
+
+        if programs.programs == initial {
+            return Ok(rounds);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    #[cfg(feature = "std")]
+    use super::Programs;
+    use super::{cycle_length, parse, solve};
+    use prelude::*;
+    #[cfg(feature = "std")]
+    use snapshot::assert_snapshot;
+    #[cfg(feature = "std")]
+    use std::fs::File;
+    #[cfg(feature = "std")]
+    use std::io::Read;
+
+    #[cfg(feature = "std")]
+    fn lineups(input: &str, num_programs: u32, repeats: u32) -> String {
+        let actions = parse(input).unwrap();
+        let mut programs = Programs::new(num_programs);
+
+        (1..=repeats)
+            .map(|round| {
+                for action in actions.iter() {
+                    programs.apply(action);
+                }
+
+                format!("{}: {}", round, programs)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
     #[test]
     fn test_cases_star_one() {
-        assert_eq!(solve("s1,x3/4,pe/b", 5, 1), "baedc");
+        assert_eq!(solve("s1,x3/4,pe/b", 5, 1), Ok("baedc".to_owned()));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_lineup_snapshot() {
+        let mut input = String::new();
+        File::open("day16.txt")
+            .and_then(|mut f| f.read_to_string(&mut input))
+            .expect("Unable to read day16.txt");
+
+        assert_snapshot("day16_example_lineups", &lineups("s1,x3/4,pe/b", 5, 4));
+        assert_snapshot("day16_input_lineups", &lineups(&input, 16, 25));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("s1,,x3/4").is_err());
+        assert!(parse("sé").is_err());
+        assert!(parse("x3").is_err());
+        assert!(parse("pa/").is_err());
+        assert!(parse("q1").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            solve("x99/100", 16, 1),
+            Err("Action x99/100 does not fit 16 programs".to_owned())
+        );
+        assert!(solve("s17", 16, 1).is_err());
+        assert!(solve("pa/q", 16, 1).is_err());
+        assert!(solve("s1", 27, 1).is_err());
+        assert_eq!(cycle_length("s1,x3/4,pe/b", 5), Ok(4));
+        assert!(cycle_length("x9/0", 5).is_err());
+        assert_eq!(
+            solve("s16,x0/15,pa/p", 16, 1),
+            Ok("abcdefghijklmnop".to_owned())
+        );
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::hash_map::DefaultHasher;
+use prelude::*;
 
 pub fn solve(step_size: usize) -> usize {
     let mut buffer = Vec::with_capacity(2018);
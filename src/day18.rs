@@ -1,18 +1,21 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
-use std::rc::Rc;
+use alloc::rc::Rc;
+use collections::{Map, VecDeque};
+use core::cell::RefCell;
+use core::convert::TryFrom;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
 
 type Register = char;
 
 #[derive(Clone, Debug)]
-enum Destination {
+pub enum Destination<T> {
     Register(Register),
-    Value(i64),
+    Value(T),
 }
 
-impl Destination {
+impl<T: Integer> Destination<T> {
     fn parse(input: &str) -> Result<Self, String> {
-        if let Ok(value) = input.parse() {
+        if let Ok(value) = input.parse::<T>() {
             Ok(Destination::Value(value))
         } else {
             if input.len() == 1 {
@@ -25,21 +28,25 @@ impl Destination {
 }
 
 #[derive(Clone, Debug)]
-enum Instruction {
-    Snd(Destination),
-    Rcv(Destination),
-    Set(Register, Destination),
-    Add(Register, Destination),
-    Mul(Register, Destination),
-    Mod(Register, Destination),
-    Jump(Destination, Destination),
+pub enum Instruction<T> {
+    Snd(Destination<T>),
+    Rcv(Destination<T>),
+    Set(Register, Destination<T>),
+    Add(Register, Destination<T>),
+    Mul(Register, Destination<T>),
+    Mod(Register, Destination<T>),
+    Jump(Destination<T>, Destination<T>),
 }
 
-impl Instruction {
-    fn parse(input: &str) -> Result<Instruction, String> {
+impl<T: Integer> Instruction<T> {
+    fn parse(input: &str) -> Result<Instruction<T>, String> {
         let cleaned = input.trim().to_lowercase();
         let parts = cleaned.split_whitespace().collect::<Vec<_>>();
 
+        if parts.is_empty() {
+            return Err(format!("Empty instruction {:?}", input));
+        }
+
         match parts[0] {
             "set" | "mul" | "add" | "mod" => {
                 let args = &parts[1..];
@@ -72,6 +79,15 @@ impl Instruction {
             "rcv" | "snd" => {
                 let args = &parts[1..];
 
+                if args.len() != 1 {
+                    return Err(format!(
+                        "Expected exactly one argument for {} instruction. Found {} in {}",
+                        parts[0],
+                        args.len(),
+                        input
+                    ));
+                }
+
                 match Destination::parse(args[0]) {
                     Ok(destination) => match parts[0] {
                         "snd" => Ok(Instruction::Snd(destination)),
@@ -102,86 +118,112 @@ impl Instruction {
     }
 }
 
-type Registers = HashMap<Register, i64>;
+pub fn parse<T: Integer>(source: &str) -> Result<Vec<Instruction<T>>, String> {
+    let instructions = source
+        .trim()
+        .lines()
+        .map(Instruction::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| {
+            format!(
+                "Failed to build program due to source code error: {}",
+                error
+            )
+        })?;
+
+    if instructions.is_empty() {
+        return Err("Program has no instructions".to_owned());
+    }
+
+    Ok(instructions)
+}
+
+type Registers<T> = Map<Register, T>;
 
-fn get_register(registers: &Registers, register: &Register) -> i64 {
-    *registers.get(register).unwrap_or(&0)
+fn get_register<T: Integer>(registers: &Registers<T>, register: &Register) -> T {
+    registers.get(register).cloned().unwrap_or_else(T::zero)
 }
 
-fn set_register(registers: &mut Registers, register: &Register, value: &Destination) {
+fn set_register<T: Integer>(
+    registers: &mut Registers<T>,
+    register: &Register,
+    value: &Destination<T>,
+) {
     let v = get_value(registers, value);
     registers.insert(register.clone(), v);
 }
 
-fn get_value(registers: &Registers, value: &Destination) -> i64 {
+fn get_value<T: Integer>(registers: &Registers<T>, value: &Destination<T>) -> T {
     match value {
         &Destination::Value(v) => v,
-        &Destination::Register(r) => *registers.get(&r).unwrap_or(&0),
+        &Destination::Register(r) => get_register(registers, &r),
     }
 }
 
-struct Program2 {
-    instructions: Vec<Instruction>,
+/// The instruction pointer `offset` instructions away from `ip`, `None` once
+/// it leaves the program.
+fn next_ip<T: Integer>(ip: usize, offset: T, len: usize) -> Result<Option<usize>, ArithmeticError> {
+    let next = (ip as i128).try_add(offset.to_i128())?;
+
+    Ok(usize::try_from(next).ok().filter(|&next| next < len))
+}
+
+struct Program2<T> {
+    instructions: Vec<Instruction<T>>,
     ip: usize,
-    registers: HashMap<Register, i64>,
+    registers: Registers<T>,
     id: usize,
-    queue: VecDeque<i64>,
-    sibling_program: Option<Rc<RefCell<Program2>>>,
+    queue: VecDeque<T>,
+    sibling_program: Option<Rc<RefCell<Program2<T>>>>,
     send_counter: usize,
+    terminated: bool,
 }
 
-impl Program2 {
+impl<T: Integer> Program2<T> {
     fn new(source: &str, id: usize) -> Result<Self, String> {
-        let instructions = source
-            .trim()
-            .lines()
-            .map(|line| Instruction::parse(line))
-            .collect::<Vec<_>>();
-        if let Some(Err(error)) = instructions
-            .clone()
-            .into_iter()
-            .find(|instruction| instruction.is_err())
-        {
-            return Err(format!(
-                "Failed to build program due to source code error: {}",
-                error
-            ));
-        }
-        let mut hash_map = HashMap::new();
-        hash_map.insert('p', id as i64);
+        let instructions = parse(source)?;
+        let mut hash_map = Map::new();
+        hash_map.insert(
+            'p',
+            T::from_usize(id).ok_or_else(|| format!("Program id {} does not fit", id))?,
+        );
 
         Ok(Self {
             id,
-            instructions: instructions.into_iter().map(|i| i.unwrap()).collect(),
+            instructions,
             ip: 0,
             registers: hash_map,
             queue: VecDeque::new(),
             sibling_program: None,
             send_counter: 0,
+            terminated: false,
         })
     }
 
-    fn current_instruction(&self) -> Instruction {
+    fn current_instruction(&self) -> Instruction<T> {
         self.instructions[self.ip].clone()
     }
 
-    fn set_sibling(&mut self, sibling: Rc<RefCell<Program2>>) {
+    fn set_sibling(&mut self, sibling: Rc<RefCell<Program2<T>>>) {
         self.sibling_program = Some(sibling);
     }
 
     fn is_deadlocked(&self) -> bool {
+        if self.terminated {
+            return true;
+        }
+
         match self.current_instruction() {
             Instruction::Rcv(_) => self.queue.is_empty(),
             _ => false,
         }
     }
 
-    fn add_to_queue(&mut self, value: i64) {
+    fn add_to_queue(&mut self, value: T) {
         self.queue.push_back(value);
     }
 
-    fn send(&mut self, destination: Destination) {
-        println!("Sending {:?} in program {}", destination, self.id);
+    fn send(&mut self, destination: Destination<T>) {
         if self.id == 1 {
             self.send_counter += 1;
         }
@@ -195,9 +237,13 @@ impl Program2 {
         ()
     }
 
-    fn tick(&mut self) {
+    fn tick(&mut self) -> Result<(), ArithmeticError> {
+        if self.terminated {
+            return Ok(());
+        }
+
         let current_instruction = self.current_instruction();
-        let mut ip_offset = 1;
+        let mut ip_offset = T::one();
         // println!(
         //     "Current instruction {:?} at ip {} in program {}",
         //     current_instruction, self.ip, self.id
@@ -207,24 +253,24 @@ impl Program2 {
             Instruction::Set(r, v) => set_register(&mut self.registers, &r, &v),
             Instruction::Add(r, v) => {
                 let new_value = Destination::Value(
-                    get_register(&mut self.registers, &r) + get_value(&self.registers, &v),
+                    get_register(&self.registers, &r).try_add(get_value(&self.registers, &v))?,
                 );
                 set_register(&mut self.registers, &r, &new_value);
             }
             Instruction::Mul(r, v) => {
                 let new_value = Destination::Value(
-                    get_register(&self.registers, &r) * get_value(&self.registers, &v),
+                    get_register(&self.registers, &r).try_mul(get_value(&self.registers, &v))?,
                 );
                 set_register(&mut self.registers, &r, &new_value);
             }
             Instruction::Mod(r, v) => {
                 let new_value = Destination::Value(
-                    get_register(&self.registers, &r) % get_value(&self.registers, &v),
+                    get_register(&self.registers, &r).try_rem(get_value(&self.registers, &v))?,
                 );
                 set_register(&mut self.registers, &r, &new_value);
             }
             Instruction::Jump(condition, offset) => {
-                if get_value(&self.registers, &condition) > 0 {
+                if get_value(&self.registers, &condition) > T::zero() {
                     ip_offset = get_value(&self.registers, &offset);
                 }
             }
@@ -232,7 +278,7 @@ impl Program2 {
             Instruction::Rcv(register) => match register {
                 Destination::Register(r) => {
                     if self.queue.is_empty() {
-                        ip_offset = 0;
+                        ip_offset = T::zero();
                     } else {
                         let value = self.queue.pop_front().unwrap();
                         set_register(&mut self.registers, &r, &Destination::Value(value));
@@ -243,55 +289,40 @@ impl Program2 {
                 }
             },
         }
-        let new_ip: i64 = (self.ip as i64) + ip_offset;
-        assert!(
-            new_ip >= 0 && (new_ip as usize) < self.instructions.len(),
-            "Invalid instruction pointer"
-        );
+        match next_ip(self.ip, ip_offset, self.instructions.len())? {
+            Some(ip) => self.ip = ip,
+            None => self.terminated = true,
+        }
 
-        self.ip = (new_ip as usize);
+        Ok(())
     }
 }
 
-struct Program1 {
-    instructions: Vec<Instruction>,
+struct Program1<T> {
+    instructions: Vec<Instruction<T>>,
     ip: usize,
-    registers: HashMap<Register, i64>,
-    last_played_frequency: Option<i64>,
-    last_recovered_frequency: Option<i64>,
+    registers: Registers<T>,
+    last_played_frequency: Option<T>,
+    last_recovered_frequency: Option<T>,
 }
 
-impl Program1 {
+impl<T: Integer> Program1<T> {
     fn new(source: &str) -> Result<Self, String> {
-        let instructions = source
-            .trim()
-            .lines()
-            .map(|line| Instruction::parse(line))
-            .collect::<Vec<_>>();
-        if let Some(Err(error)) = instructions
-            .clone()
-            .into_iter()
-            .find(|instruction| instruction.is_err())
-        {
-            return Err(format!(
-                "Failed to build program due to source code error: {}",
-                error
-            ));
-        }
+        let instructions = parse(source)?;
 
         Ok(Self {
-            instructions: instructions.into_iter().map(|i| i.unwrap()).collect(),
+            instructions,
             ip: 0,
-            registers: HashMap::new(),
+            registers: Map::new(),
             last_played_frequency: None,
             last_recovered_frequency: None,
         })
     }
 
-    fn run(&mut self) {
+    fn run(&mut self) -> Result<(), ArithmeticError> {
         while self.last_recovered_frequency.is_none() {
             let current_instruction = self.instructions[self.ip].clone();
-            let mut ip_offset = 1;
+            let mut ip_offset = T::one();
             // println!(
             //     "Current instruction {:?} at ip {}",
             //     current_instruction, self.ip
@@ -301,24 +332,27 @@ impl Program1 {
                 Instruction::Set(r, v) => set_register(&mut self.registers, &r, &v),
                 Instruction::Add(r, v) => {
                     let new_value = Destination::Value(
-                        get_register(&mut self.registers, &r) + get_value(&self.registers, &v),
+                        get_register(&self.registers, &r)
+                            .try_add(get_value(&self.registers, &v))?,
                     );
                     set_register(&mut self.registers, &r, &new_value);
                 }
                 Instruction::Mul(r, v) => {
                     let new_value = Destination::Value(
-                        get_register(&self.registers, &r) * get_value(&self.registers, &v),
+                        get_register(&self.registers, &r)
+                            .try_mul(get_value(&self.registers, &v))?,
                     );
                     set_register(&mut self.registers, &r, &new_value);
                 }
                 Instruction::Mod(r, v) => {
                     let new_value = Destination::Value(
-                        get_register(&self.registers, &r) % get_value(&self.registers, &v),
+                        get_register(&self.registers, &r)
+                            .try_rem(get_value(&self.registers, &v))?,
                     );
                     set_register(&mut self.registers, &r, &new_value);
                 }
                 Instruction::Jump(condition, offset) => {
-                    if get_value(&self.registers, &condition) > 0 {
+                    if get_value(&self.registers, &condition) > T::zero() {
                         ip_offset = get_value(&self.registers, &offset);
                     }
                 }
@@ -326,62 +360,58 @@ impl Program1 {
                     self.last_played_frequency = Some(get_value(&self.registers, &v))
                 }
                 Instruction::Rcv(condition) => {
-                    if get_value(&self.registers, &condition) != 0 {
+                    if get_value(&self.registers, &condition) != T::zero() {
                         self.last_recovered_frequency = self.last_played_frequency;
                     }
                 }
             }
-            let new_ip: i64 = (self.ip as i64) + ip_offset;
-            assert!(
-                new_ip >= 0 && (new_ip as usize) < self.instructions.len(),
-                "Invalid instruction pointer"
-            );
-
-            self.ip = (new_ip as usize);
+            match next_ip(self.ip, ip_offset, self.instructions.len())? {
+                Some(ip) => self.ip = ip,
+                None => break,
+            }
         }
+
+        Ok(())
     }
 }
 
-pub fn solve(input: &str) -> i64 {
-    let mut program = match Program1::new(input) {
-        Ok(program) => program,
-        Err(error) => panic!(error),
-    };
-
-    program.run();
+pub fn solve<T: Integer>(input: &str) -> Result<T, String> {
+    let mut program = Program1::<T>::new(input)?;
+    program
+        .run()
+        .map_err(|error| format!("Program failed: {}", error))?;
 
     program
         .last_recovered_frequency
-        .expect("rcv should been called at least once")
+        .ok_or_else(|| "rcv should been called at least once".to_owned())
 }
 
-pub fn solve2(input: &str) -> usize {
-    let mut program1 = match Program2::new(input, 0) {
-        Ok(program) => Rc::new(RefCell::new(program)),
-        Err(error) => panic!(error),
-    };
-
-    let mut program2 = match Program2::new(input, 1) {
-        Ok(program) => Rc::new(RefCell::new(program)),
-        Err(error) => panic!(error),
-    };
+pub fn solve2<T: Integer>(input: &str) -> Result<usize, String> {
+    let program1 = Rc::new(RefCell::new(Program2::<T>::new(input, 0)?));
+    let program2 = Rc::new(RefCell::new(Program2::<T>::new(input, 1)?));
 
     program1.borrow_mut().set_sibling(program2.clone());
     program2.borrow_mut().set_sibling(program1.clone());
 
     while !program1.borrow().is_deadlocked() || !program2.borrow().is_deadlocked() {
-        program1.borrow_mut().tick();
-        program2.borrow_mut().tick();
+        for program in &[&program1, &program2] {
+            program
+                .borrow_mut()
+                .tick()
+                .map_err(|error| format!("Program failed: {}", error))?;
+        }
     }
 
     let result = program2.borrow().send_counter;
 
-    result
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{solve, solve2};
+    use super::{parse, solve, solve2, Program1};
+    use integer::ArithmeticError;
+    use prelude::*;
 
     #[test]
     fn test_cases_star_one() {
@@ -398,7 +428,7 @@ mod tests {
                 jgz a -2
                 ";
 
-        assert_eq!(solve(input), 4);
+        assert_eq!(solve::<i64>(input), Ok(4));
     }
 
     #[test]
@@ -412,6 +442,74 @@ mod tests {
                 rcv c
                 rcv d
                 ";
-        assert_eq!(solve2(input), 3);
+        assert_eq!(solve2::<i64>(input), Ok(3));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let mut program = Program1::<i64>::new(
+            "
+                set a 9223372036854775807
+                mul a 2
+                ",
+        )
+        .unwrap();
+        assert_eq!(program.run(), Err(ArithmeticError::Overflow));
+
+        let mut program = Program1::<i64>::new(
+            "
+                set a 1
+                mod a b
+                ",
+        )
+        .unwrap();
+        assert_eq!(program.run(), Err(ArithmeticError::DivisionByZero));
+        assert_eq!(
+            solve::<i64>("set a 1\nmod a b"),
+            Err("Program failed: Division by zero".to_owned())
+        );
+        assert!(solve2::<i64>("snd").is_err());
+    }
+
+    #[test]
+    fn test_wider_integers() {
+        let input = "set a 9223372036854775807\nmul a 2\nsnd a\nrcv a";
+
+        assert_eq!(
+            solve::<i64>(input),
+            Err("Program failed: Arithmetic overflow".to_owned())
+        );
+        assert_eq!(solve::<i128>(input), Ok(18_446_744_073_709_551_614));
+        assert_eq!(solve::<i32>("snd 7\nrcv 1"), Ok(7));
+    }
+
+    #[test]
+    fn test_jumps() {
+        let input = "set a 1\njgz 1 170141183460469231731687303715884105727";
+        let mut program = Program1::<i128>::new(input).unwrap();
+        assert_eq!(program.run(), Err(ArithmeticError::Overflow));
+        assert_eq!(
+            solve2::<i128>(input),
+            Err("Program failed: Arithmetic overflow".to_owned())
+        );
+        assert_eq!(solve2::<i64>("set a 1\njgz 1 9223372036854775807"), Ok(0));
+
+        // Jumping out of the program terminates it.
+        assert_eq!(
+            solve::<i64>("snd 1\njgz 1 -2"),
+            Err("rcv should been called at least once".to_owned())
+        );
+        assert_eq!(solve2::<i64>("snd 1\njgz 1 5"), Ok(1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse::<i64>("").is_err());
+        assert!(parse::<i64>("set a 1\n\nsnd a").is_err());
+        assert!(parse::<i64>("snd").is_err());
+        assert!(parse::<i64>("rcv a b").is_err());
+        assert!(parse::<i64>("jgz a").is_err());
+        assert!(parse::<i64>("set ab 1").is_err());
+        assert!(parse::<i32>("set a 9223372036854775807").is_err());
     }
 }
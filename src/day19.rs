@@ -1,103 +1,88 @@
-use std::collections::HashSet;
+use collections::Set;
+use core::convert::TryFrom;
+use prelude::*;
 
-type Location = (usize, usize);
+type Location = (i64, i64);
+type Direction = (i64, i64);
 
-fn is_valid_location(location: &Location, grid: &Vec<Vec<char>>) -> bool {
-    location.1 >= 0
-        && location.1 < grid.len()
-        && location.0 >= 0
-        && location.0 < grid[location.1].len()
+/// The character at `location`, a space outside of the grid.
+fn at(grid: &[Vec<char>], location: Location) -> char {
+    usize::try_from(location.1)
+        .ok()
+        .and_then(|y| grid.get(y))
+        .and_then(|row| usize::try_from(location.0).ok().and_then(|x| row.get(x)))
+        .cloned()
+        .unwrap_or(' ')
 }
 
-pub fn solve(input: &str) -> (String, u64) {
+fn step(location: Location, direction: Direction) -> Location {
+    (location.0 + direction.0, location.1 + direction.1)
+}
+
+pub fn parse(input: &str) -> Result<Vec<Vec<char>>, String> {
     let grid: Vec<Vec<char>> = input
         .lines()
-        .filter(|line| line.len() != 0)
+        .filter(|line| !line.is_empty())
         .map(|line| line.chars().collect())
         .collect();
 
-    let mut current_position = (
-        grid[0]
-            .iter()
-            .position(|&c| c == '|')
-            .expect("Expected `|` on the first line"),
-        0,
-    );
-    let mut previous_positions = HashSet::<(usize, usize)>::new();
-    let mut direction: (i64, i64) = (0, 1);
-    let mut cont = true;
+    match grid.first() {
+        Some(row) if row.contains(&'|') => Ok(grid),
+        Some(_) => Err("Expected `|` on the first line".to_owned()),
+        None => Err("Expected at least one line".to_owned()),
+    }
+}
+
+/// Follows the path from the `|` on the first line until it runs onto a
+/// space, collecting letters and counting steps. Turns only ever go to cells
+/// not visited before, so the walk always ends.
+pub fn solve(input: &str) -> Result<(String, u64), String> {
+    let grid = parse(input)?;
+
+    let start = grid[0].iter().position(|&c| c == '|').unwrap_or(0);
+    let mut current_position: Location = (start as i64, 0);
+    let mut previous_positions = Set::<Location>::new();
+    let mut direction: Direction = (0, 1);
     let mut seen_letters = Vec::new();
     let mut count = 0;
+    let open = |location: Location| !at(&grid, location).is_whitespace();
 
-    while cont {
+    loop {
+        let value = at(&grid, current_position);
+        if value.is_whitespace() {
+            break;
+        }
         previous_positions.insert(current_position);
-        let potential_next = (
-            (current_position.0 as i64 + direction.0) as usize,
-            (current_position.1 as i64 + direction.1) as usize,
-        );
-        let value = grid[current_position.1][current_position.0];
+
         match value {
-            '|' | '-' => {
-                current_position = potential_next;
-            }
+            '|' | '-' => {}
             '+' => {
-                let left = (current_position.0 - 1, current_position.1);
-                let right = (current_position.0 + 1, current_position.1);
-                let top = (current_position.0, current_position.1 - 1);
-                let bottom = (current_position.0, current_position.1 + 1);
-
-                if is_valid_location(&potential_next, &grid)
-                    && !grid[potential_next.1][potential_next.0].is_whitespace()
-                {
-                    current_position = potential_next;
-                } else if is_valid_location(&left, &grid) && !(grid[left.1][left.0].is_whitespace()
-                    || previous_positions.contains(&left))
-                {
-                    current_position = left;
-                    direction = (-1, 0);
-                } else if is_valid_location(&right, &grid) && !(grid[right.1][right.0]
-                    .is_whitespace()
-                    || previous_positions.contains(&right))
-                {
-                    current_position = right;
-                    direction = (1, 0);
-                } else if is_valid_location(&top, &grid) && !(grid[top.1][top.0].is_whitespace()
-                    || previous_positions.contains(&top))
-                {
-                    current_position = top;
-                    direction = (0, -1);
-                } else if is_valid_location(&bottom, &grid) && !(grid[bottom.1][bottom.0]
-                    .is_whitespace()
-                    || previous_positions.contains(&bottom))
-                {
-                    current_position = bottom;
-                    direction = (0, 1);
-                } else {
-                    assert!(false, "This is bad");
-                }
-            }
-            _ => {
-                if value.is_alphabetic() {
-                    seen_letters.push(value);
-                    current_position = potential_next;
-
-                    if grid[current_position.1][current_position.0].is_whitespace() {
-                        cont = false;
-                    }
-                } else {
-                    assert!(false, "Shouldn't be here");
+                if !open(step(current_position, direction)) {
+                    direction = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .cloned()
+                        .find(|&turn| {
+                            let next = step(current_position, turn);
+                            open(next) && !previous_positions.contains(&next)
+                        })
+                        .ok_or_else(|| format!("Dead end at {:?}", current_position))?;
                 }
             }
+            c if c.is_alphabetic() => seen_letters.push(c),
+            c => return Err(format!("Unexpected {:?} at {:?}", c, current_position)),
         }
+
+        current_position = step(current_position, direction);
         count += 1;
     }
 
-    (seen_letters.iter().collect::<String>(), count)
+    Ok((seen_letters.iter().collect::<String>(), count))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::{parse, solve};
+    use prelude::*;
 
     #[test]
     fn test_cases_star_one() {
@@ -109,6 +94,18 @@ mod tests {
      |  |  |  D
      +B-+  +--+
      ";
-        assert_eq!(solve(input), (String::from("ABCDEF"), 38));
+        assert_eq!(solve(input), Ok((String::from("ABCDEF"), 38)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("").is_err());
+        assert_eq!(
+            solve("  -\n  |"),
+            Err("Expected `|` on the first line".to_owned())
+        );
+        assert_eq!(solve("|\n+"), Err("Dead end at (0, 1)".to_owned()));
+        assert_eq!(solve("|\n*"), Err("Unexpected '*' at (0, 1)".to_owned()));
+        assert_eq!(solve("|\nA"), Ok((String::from("A"), 2)));
     }
 }
@@ -1,45 +1,400 @@
-fn permutate(values: &Vec<i32>) -> Vec<[i32; 2]> {
-    values
-        .iter()
-        .enumerate()
-        .flat_map(move |(i, &v)| {
-            values
+use core::convert::TryFrom;
+use core::fmt;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Comma separated cells, a cell may be quoted to contain commas and
+    /// `""` within quotes is a literal quote.
+    Csv,
+    Tsv,
+    Whitespace,
+}
+
+/// What to do with cells that have no value, e.g. `1,,3` in a CSV row.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EmptyCells {
+    Skip,
+    Zero,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Orientation {
+    Rows,
+    Columns,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Options {
+    pub format: Format,
+    pub header: bool,
+    pub empty_cells: EmptyCells,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Format::Whitespace,
+            header: false,
+            empty_cells: EmptyCells::Skip,
+        }
+    }
+}
+
+/// An invalid cell, `row` is the 1-based line in the input and `column` the
+/// 1-based cell within that line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CellError {
+    pub row: usize,
+    pub column: usize,
+    pub value: String,
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid value {:?} at row {}, column {}",
+            self.value, self.row, self.column
+        )
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Checksum {
+    pub total: i32,
+    /// The value of every row or column in order, empty ones contribute 0.
+    pub contributions: Vec<i32>,
+}
+
+#[derive(Debug)]
+pub struct Spreadsheet {
+    pub header: Option<Vec<String>>,
+    rows: Vec<Vec<Option<i32>>>,
+}
+
+/// Splits `rest` at its first comma.
+fn split_comma(rest: &str) -> (&str, Option<&str>) {
+    match rest.find(',') {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    }
+}
+
+/// The cells of a CSV line, or the 1-based column and text of a quoted cell
+/// that is never closed or has text after its closing quote.
+fn split_csv(line: &str) -> Result<Vec<String>, (usize, String)> {
+    let mut cells = Vec::new();
+    let mut rest = line;
+
+    loop {
+        let trimmed = rest.trim_start();
+        let (cell, remainder) = if trimmed.starts_with('"') {
+            let mut cell = String::new();
+            let mut chars = trimmed.char_indices().skip(1).peekable();
+            let end = loop {
+                match chars.next() {
+                    Some((_, '"')) if chars.peek().map(|&(_, c)| c) == Some('"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    Some((i, '"')) => break i + 1,
+                    Some((_, c)) => cell.push(c),
+                    None => return Err((cells.len() + 1, trimmed.to_owned())),
+                }
+            };
+
+            let (after, remainder) = split_comma(&trimmed[end..]);
+            if !after.trim().is_empty() {
+                return Err((cells.len() + 1, trimmed[..end + after.len()].to_owned()));
+            }
+            (cell, remainder)
+        } else {
+            let (cell, remainder) = split_comma(trimmed);
+            (cell.trim().to_owned(), remainder)
+        };
+
+        cells.push(cell);
+        match remainder {
+            Some(remainder) => rest = remainder,
+            None => return Ok(cells),
+        }
+    }
+}
+
+impl Spreadsheet {
+    pub fn parse(input: &str, options: &Options) -> Result<Spreadsheet, CellError> {
+        let mut header = None;
+        let mut rows = Vec::new();
+
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let cells: Vec<String> = match options.format {
+                Format::Csv => split_csv(line).map_err(|(column, value)| CellError {
+                    row: i + 1,
+                    column,
+                    value,
+                })?,
+                Format::Tsv => line
+                    .split('\t')
+                    .map(|cell| cell.trim().to_owned())
+                    .collect(),
+                Format::Whitespace => line.split_whitespace().map(ToOwned::to_owned).collect(),
+            };
+
+            if options.header && header.is_none() {
+                header = Some(cells);
+                continue;
+            }
+
+            let row = cells
                 .iter()
                 .enumerate()
-                .map(move |(ii, &vv)| if i == ii { None } else { Some([v, vv]) })
-                .filter(|x| x.is_some())
-                .map(|x| x.unwrap())
-        }).collect()
+                .map(|(j, cell)| {
+                    let error = || CellError {
+                        row: i + 1,
+                        column: j + 1,
+                        value: cell.to_string(),
+                    };
+
+                    match (cell.is_empty(), options.empty_cells) {
+                        (true, EmptyCells::Skip) => Ok(None),
+                        (true, EmptyCells::Zero) => Ok(Some(0)),
+                        (true, EmptyCells::Error) => Err(error()),
+                        (false, _) => cell.parse::<i32>().map(Some).map_err(|_| error()),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+
+        Ok(Spreadsheet { header, rows })
+    }
+
+    pub fn rows(&self) -> Vec<Vec<i32>> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().filter_map(|&cell| cell).collect())
+            .collect()
+    }
+
+    pub fn columns(&self) -> Vec<Vec<i32>> {
+        let width = self.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        (0..width)
+            .map(|j| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(j).and_then(|&cell| cell))
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn checksum(
+        &self,
+        orientation: Orientation,
+        data_for_row: &dyn Fn(&[i32]) -> Result<i32, ArithmeticError>,
+    ) -> Result<Checksum, ArithmeticError> {
+        let lines = match orientation {
+            Orientation::Rows => self.rows(),
+            Orientation::Columns => self.columns(),
+        };
+        let contributions = lines
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    Ok(0)
+                } else {
+                    data_for_row(line)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Checksum {
+            total: contributions
+                .iter()
+                .try_fold(0i32, |total, &contribution| total.try_add(contribution))?,
+            contributions,
+        })
+    }
+}
+
+/// The difference between the largest and smallest value, 0 for an empty
+/// row.
+pub fn row_data_min_max(row: &[i32]) -> Result<i32, ArithmeticError> {
+    match (row.iter().max(), row.iter().min()) {
+        (Some(max), Some(min)) => max.try_sub(*min),
+        _ => Ok(0),
+    }
 }
 
-pub fn row_data_min_max(row: &Vec<i32>) -> i32 {
-    row.iter().max().unwrap() - row.iter().min().unwrap()
+/// Cells of a row that share an absolute value.
+struct Group {
+    value: i64,
+    positive: i64,
+    negative: i64,
+}
+
+impl Group {
+    fn instances(&self) -> Vec<i32> {
+        let mut instances = vec![self.value as i32; self.positive as usize];
+        instances.extend(vec![(-self.value) as i32; self.negative as usize]);
+
+        instances
+    }
 }
 
-pub fn row_data_evenly_divisible(row: &Vec<i32>) -> i32 {
-    permutate(row)
+fn groups(row: &[i32]) -> Vec<Group> {
+    let mut values = row.to_vec();
+    values.sort_by_key(|value| i64::from(*value).abs());
+
+    let mut groups: Vec<Group> = Vec::new();
+    for value in values {
+        let value = i64::from(value);
+        if groups.last().map(|group| group.value) != Some(value.abs()) {
+            groups.push(Group {
+                value: value.abs(),
+                positive: 0,
+                negative: 0,
+            });
+        }
+
+        let group = groups.last_mut().unwrap();
+        if value < 0 {
+            group.negative += 1;
+        } else {
+            group.positive += 1;
+        }
+    }
+
+    groups
+}
+
+/// Calls `f(dividend, divisor)` for every pair of groups where the divisor
+/// evenly divides the dividend. Every divisor sieves through its multiples
+/// up to a limit and scans the groups above it. The limit is the group value
+/// that minimises the total of both, so dense small values are sieved while
+/// a few large outliers are only scanned.
+fn for_each_divisible<F>(groups: &[Group], mut f: F)
+where
+    F: FnMut(&Group, &Group),
+{
+    let divisors = groups
+        .iter()
+        .filter(|group| group.value != 0)
+        .collect::<Vec<_>>();
+    // Sieving up to `limit` costs about `limit * harmonic` steps over all
+    // divisors, scanning `count` groups costs `count * divisors.len()`.
+    let harmonic = divisors
         .iter()
-        .filter(|values| values[0] % values[1] == 0)
-        .map(|v| v[0] / v[1])
-        .sum()
+        .map(|divisor| 1.0 / divisor.value as f64)
+        .sum::<f64>();
+    let cost = |split: usize| {
+        let limit = if split == 0 {
+            0
+        } else {
+            groups[split - 1].value
+        };
+        limit as f64 * harmonic + (groups.len() - split) as f64 * divisors.len() as f64
+    };
+    let split = (0..=groups.len())
+        .min_by(|&a, &b| cost(a).partial_cmp(&cost(b)).unwrap())
+        .unwrap_or(0);
+    let (sieved, scanned) = groups.split_at(split);
+
+    for divisor in divisors {
+        if let Some(limit) = sieved.last().map(|group| group.value) {
+            let mut multiple = 0;
+            while multiple <= limit {
+                if let Ok(i) = sieved.binary_search_by_key(&multiple, |group| group.value) {
+                    f(&sieved[i], divisor);
+                }
+                multiple += divisor.value;
+            }
+        }
+
+        scanned
+            .iter()
+            .filter(|group| group.value % divisor.value == 0)
+            .for_each(|dividend| f(dividend, divisor));
+    }
+}
+
+/// Every ordered `(dividend, divisor)` pair of cells at different positions
+/// where the divisor evenly divides the dividend. Zero divides nothing and is
+/// divisible by every other value, signs follow integer division.
+pub fn divisible_pairs(row: &[i32]) -> Vec<(i32, i32)> {
+    let groups = groups(row);
+    let mut pairs = Vec::new();
+    // `for_each_divisible` goes through the dividends of one divisor at a
+    // time, so its instances are built once and reused until it changes.
+    let mut divisor_instances: (Option<i64>, Vec<i32>) = (None, Vec::new());
+
+    for_each_divisible(&groups, |dividend, divisor| {
+        let same = dividend.value == divisor.value;
+        if divisor_instances.0 != Some(divisor.value) {
+            divisor_instances = (Some(divisor.value), divisor.instances());
+        }
+
+        for (i, &x) in dividend.instances().iter().enumerate() {
+            for (j, &y) in divisor_instances.1.iter().enumerate() {
+                if !same || i != j {
+                    pairs.push((x, y));
+                }
+            }
+        }
+    });
+
+    pairs
+}
+
+/// The sum of the quotients of all `divisible_pairs`, computed from the
+/// group counts without listing the pairs.
+pub fn divisible_quotient_sum(row: &[i32]) -> i128 {
+    let groups = groups(row);
+    let mut sum = 0;
+
+    for_each_divisible(&groups, |dividend, divisor| {
+        let quotient = i128::from(dividend.value / divisor.value);
+        let dividend_sign = i128::from(dividend.positive - dividend.negative);
+        let divisor_sign = i128::from(divisor.positive - divisor.negative);
+
+        sum += if dividend.value == divisor.value {
+            dividend_sign * dividend_sign - i128::from(dividend.positive + dividend.negative)
+        } else {
+            quotient * dividend_sign * divisor_sign
+        };
+    });
+
+    sum
+}
+
+/// The `divisible_quotient_sum` of a row, an error when it does not fit in
+/// an `i32`.
+pub fn row_data_evenly_divisible(row: &[i32]) -> Result<i32, ArithmeticError> {
+    i32::try_from(divisible_quotient_sum(row)).map_err(|_| ArithmeticError::Overflow)
 }
 
-pub fn solve(input: &str, data_for_row: &Fn(&Vec<i32>) -> i32) -> i32 {
-    input
-        .split("\n")
-        .map(|row| {
-            row.trim()
-                .split_whitespace()
-                .map(|v| v.parse::<i32>().unwrap())
-                .collect::<Vec<i32>>()
-        }).filter(|row| !row.is_empty())
-        .map(|row| data_for_row(&row))
-        .sum()
+pub fn solve(
+    input: &str,
+    data_for_row: &dyn Fn(&[i32]) -> Result<i32, ArithmeticError>,
+) -> Result<i32, String> {
+    Spreadsheet::parse(input, &Options::default())
+        .map_err(|error| error.to_string())?
+        .checksum(Orientation::Rows, data_for_row)
+        .map(|checksum| checksum.total)
+        .map_err(|error| error.to_string())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{row_data_evenly_divisible, row_data_min_max, solve};
+    use super::*;
 
     #[test]
     fn test_cases_star_one() {
@@ -52,7 +407,7 @@ mod tests {
             ",
                 &row_data_min_max
             ),
-            18
+            Ok(18)
         );
     }
 
@@ -67,14 +422,227 @@ mod tests {
             ",
                 &row_data_evenly_divisible
             ),
-            9
+            Ok(9)
         );
     }
 
     #[test]
     fn test_row_data_evenly_divisible() {
-        assert_eq!(row_data_evenly_divisible(&vec![5, 9, 2, 8]), 4);
-        assert_eq!(row_data_evenly_divisible(&vec![9, 4, 7, 3]), 3);
-        assert_eq!(row_data_evenly_divisible(&vec![3, 8, 6, 5]), 2);
+        assert_eq!(row_data_evenly_divisible(&[5, 9, 2, 8]), Ok(4));
+        assert_eq!(row_data_evenly_divisible(&[9, 4, 7, 3]), Ok(3));
+        assert_eq!(row_data_evenly_divisible(&[3, 8, 6, 5]), Ok(2));
+    }
+
+    #[test]
+    fn test_row_data_overflow() {
+        assert_eq!(
+            row_data_evenly_divisible(&[i32::MIN, -1]),
+            Err(ArithmeticError::Overflow)
+        );
+        assert_eq!(
+            row_data_min_max(&[i32::MIN, i32::MAX]),
+            Err(ArithmeticError::Overflow)
+        );
+        assert_eq!(
+            solve("1 2\n-2147483648 -1", &row_data_evenly_divisible),
+            Err("Arithmetic overflow".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        let csv = Options {
+            format: Format::Csv,
+            header: true,
+            empty_cells: EmptyCells::Skip,
+        };
+        let sheet = Spreadsheet::parse("a,b,c\n5,1,9\n7,,3\n\n2,4,6", &csv).unwrap();
+
+        assert_eq!(
+            sheet.header,
+            Some(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()])
+        );
+        assert_eq!(sheet.rows(), vec![vec![5, 1, 9], vec![7, 3], vec![2, 4, 6]]);
+        assert_eq!(
+            sheet.columns(),
+            vec![vec![5, 7, 2], vec![1, 4], vec![9, 3, 6]]
+        );
+
+        let tsv = Options {
+            format: Format::Tsv,
+            empty_cells: EmptyCells::Zero,
+            ..Options::default()
+        };
+        let sheet = Spreadsheet::parse("5\t1\t9\n7\t\t3", &tsv).unwrap();
+        assert_eq!(sheet.rows(), vec![vec![5, 1, 9], vec![7, 0, 3]]);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let sheet = Spreadsheet::parse("5 1 9 5\n7 5 3\n2 4 6 8", &Options::default()).unwrap();
+
+        let rows = sheet
+            .checksum(Orientation::Rows, &row_data_min_max)
+            .unwrap();
+        assert_eq!(rows.total, 18);
+        assert_eq!(rows.contributions, vec![8, 4, 6]);
+
+        let columns = sheet
+            .checksum(Orientation::Columns, &row_data_min_max)
+            .unwrap();
+        assert_eq!(columns.total, 18);
+        assert_eq!(columns.contributions, vec![5, 4, 6, 3]);
+
+        let sheet = Spreadsheet::parse("0 2147483647\n0 1", &Options::default()).unwrap();
+        assert_eq!(
+            sheet.checksum(Orientation::Rows, &row_data_min_max),
+            Err(ArithmeticError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_cell_errors() {
+        let error = Spreadsheet::parse("\n1 2\n3 x 4", &Options::default()).unwrap_err();
+        assert_eq!(
+            error,
+            CellError {
+                row: 3,
+                column: 2,
+                value: "x".to_owned(),
+            }
+        );
+        assert_eq!(error.to_string(), "Invalid value \"x\" at row 3, column 2");
+
+        let strict = Options {
+            format: Format::Csv,
+            empty_cells: EmptyCells::Error,
+            ..Options::default()
+        };
+        assert_eq!(Spreadsheet::parse("1,,3", &strict).unwrap_err().column, 2);
+
+        let csv = Options {
+            format: Format::Csv,
+            ..Options::default()
+        };
+        assert_eq!(
+            Spreadsheet::parse("1,\"2,3", &csv).unwrap_err(),
+            CellError {
+                row: 1,
+                column: 2,
+                value: "\"2,3".to_owned(),
+            }
+        );
+        assert_eq!(
+            Spreadsheet::parse("1, \"2\" 3,4", &csv).unwrap_err(),
+            CellError {
+                row: 1,
+                column: 2,
+                value: "\"2\" 3".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_quoted_csv() {
+        let csv = Options {
+            format: Format::Csv,
+            header: true,
+            empty_cells: EmptyCells::Zero,
+        };
+        let sheet = Spreadsheet::parse(
+            "\"a, b\",\"say \"\"c\"\"\",d\n\"5\", 1 ,\"\"\n7,\"2\",3",
+            &csv,
+        )
+        .unwrap();
+
+        assert_eq!(
+            sheet.header,
+            Some(vec![
+                "a, b".to_owned(),
+                "say \"c\"".to_owned(),
+                "d".to_owned()
+            ])
+        );
+        assert_eq!(sheet.rows(), vec![vec![5, 1, 0], vec![7, 2, 3]]);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(row_data_evenly_divisible(&[0, 4, 3]), Ok(0));
+    }
+
+    fn brute_force(row: &[i32]) -> Vec<(i32, i32)> {
+        let mut pairs = Vec::new();
+        for (i, &x) in row.iter().enumerate() {
+            for (j, &y) in row.iter().enumerate() {
+                if i != j && y != 0 && i64::from(x) % i64::from(y) == 0 {
+                    pairs.push((x, y));
+                }
+            }
+        }
+        pairs.sort();
+
+        pairs
+    }
+
+    #[test]
+    fn test_divisible_pairs() {
+        let rows: Vec<Vec<i32>> = vec![
+            vec![],
+            vec![5, 9, 2, 8],
+            vec![2, 2, 4, -2, 0, 0],
+            vec![-6, 3, -3, 1, 7, -1, 12],
+            vec![i32::MIN, -1, 1, 2, i32::MAX],
+        ];
+
+        for row in rows {
+            let mut pairs = divisible_pairs(&row);
+            pairs.sort();
+            let expected = brute_force(&row);
+            let sum = expected
+                .iter()
+                .map(|&(x, y)| i128::from(x) / i128::from(y))
+                .sum::<i128>();
+
+            assert_eq!(pairs, expected);
+            assert_eq!(divisible_quotient_sum(&row), sum);
+        }
+    }
+
+    #[test]
+    fn test_large_row() {
+        let size = 50_000;
+        let row = (1..=size).collect::<Vec<i32>>();
+        let expected = (1..=size as i128)
+            .map(|divisor| (1..=size as i128 / divisor).sum::<i128>() - 1)
+            .sum::<i128>();
+
+        assert_eq!(divisible_quotient_sum(&row), expected);
+    }
+
+    #[test]
+    fn test_large_row_with_outlier() {
+        let size = 200_000;
+        let outlier = 1_000_000_000;
+        let mut row = (1..=size).collect::<Vec<i32>>();
+        row.push(outlier);
+        let expected = (1..=size as i128)
+            .map(|divisor| {
+                let multiples = (1..=size as i128 / divisor).sum::<i128>() - 1;
+                if outlier as i128 % divisor == 0 {
+                    multiples + outlier as i128 / divisor
+                } else {
+                    multiples
+                }
+            })
+            .sum::<i128>();
+
+        assert_eq!(divisible_quotient_sum(&row), expected);
+    }
+
+    #[test]
+    fn test_empty_row() {
+        assert_eq!(row_data_min_max(&[]), Ok(0));
+        assert_eq!(row_data_evenly_divisible(&[]), Ok(0));
     }
 }
@@ -1,8 +1,9 @@
-use std::collections::{HashMap, HashSet};
-use std::ops::Add;
+use collections::{Map, Set};
+use core::ops::Add;
+use prelude::*;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-struct Vector {
+pub struct Vector {
     x: i64,
     y: i64,
     z: i64,
@@ -13,22 +14,29 @@ impl Vector {
         Self { x, y, z }
     }
 
-    fn from_string(input: String) -> Self {
+    fn from_string(input: String) -> Result<Self, String> {
         let numbers: Vec<i64> = input
-            .replace("<", "")
-            .replace(">", "")
-            .split(",")
-            .map(|value| value.parse::<i64>().expect("Invalid numer"))
-            .collect();
-
-        assert!(
-            numbers.len() == 3,
-            "Expected exactly three numbers per vector got {} for {}",
-            numbers.len(),
-            input
-        );
-
-        Self::new(numbers[0], numbers[1], numbers[2])
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("Invalid number {:?} in {}", value, input))
+            })
+            .collect::<Result<_, _>>()?;
+
+        if numbers.len() != 3 {
+            return Err(format!(
+                "Expected exactly three numbers per vector got {} for {}",
+                numbers.len(),
+                input
+            ));
+        }
+
+        Ok(Self::new(numbers[0], numbers[1], numbers[2]))
     }
 
     fn manhattan_distance_to_origin(&self) -> i64 {
@@ -45,7 +53,7 @@ impl Add for Vector {
 }
 
 #[derive(Debug)]
-struct Particle {
+pub struct Particle {
     pub position: Vector,
     velocity: Vector,
     acceleration: Vector,
@@ -66,35 +74,37 @@ impl Particle {
     }
 }
 
-fn parse(input: &str) -> Vec<Particle> {
-    input
-        .split("\n")
-        .filter(|line| line.len() > 0)
-        .map(|line| {
-            let values = line
-                .split(", ")
-                .map(|definition| {
-                    let parts: Vec<&str> = definition.split("=").collect();
-
-                    assert!(
-                        parts.len() == 2,
-                        "Invalid particle definition {}",
-                        definition
-                    );
-
-                    Vector::from_string(parts[1].to_owned())
-                })
-                .collect::<Vec<Vector>>();
-
-            assert!(values.len() == 3, "Each particle should have 3 values.");
-
-            Particle::new(values[0], values[1], values[2])
+fn parse_particle(line: &str) -> Result<Particle, String> {
+    let values = line
+        .split(", ")
+        .map(|definition| {
+            let parts: Vec<&str> = definition.split('=').collect();
+
+            if parts.len() != 2 {
+                return Err(format!("Invalid particle definition {}", definition));
+            }
+
+            Vector::from_string(parts[1].to_owned())
         })
+        .collect::<Result<Vec<Vector>, _>>()?;
+
+    if values.len() != 3 {
+        return Err(format!("Each particle should have 3 values in {}", line));
+    }
+
+    Ok(Particle::new(values[0], values[1], values[2]))
+}
+
+pub fn parse(input: &str) -> Result<Vec<Particle>, String> {
+    input
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_particle)
         .collect()
 }
 
 pub fn star_one(input: &str) -> usize {
-    let mut particles = parse(input);
+    let mut particles = parse(input).expect("Invalid particles");
 
     // 1000 iterations is just an arbitrary choice
     for _ in 1..1000 {
@@ -115,7 +125,11 @@ pub fn star_one(input: &str) -> usize {
 }
 
 pub fn star_two(input: &str) -> usize {
-    let mut particles: Vec<Option<Particle>> = parse(input).into_iter().map(|p| Some(p)).collect();
+    let mut particles: Vec<Option<Particle>> = parse(input)
+        .expect("Invalid particles")
+        .into_iter()
+        .map(Some)
+        .collect();
 
     // 1000 iterations is just an arbitrary choice
     for _ in 1..1000 {
@@ -123,7 +137,7 @@ pub fn star_two(input: &str) -> usize {
             particle.as_mut().map(|ref mut p| p.tick());
         }
 
-        let mut particles_by_distance_to_origin = HashMap::<i64, Vec<usize>>::new();
+        let mut particles_by_distance_to_origin = Map::<i64, Vec<usize>>::new();
         particles
             .iter()
             .enumerate()
@@ -137,7 +151,7 @@ pub fn star_two(input: &str) -> usize {
 
         particles_by_distance_to_origin.iter().for_each(|(_, ids)| {
             if ids.len() > 1 {
-                let mut confirmed_collisions = HashSet::new();
+                let mut confirmed_collisions = Set::new();
 
                 for id1 in ids {
                     for id2 in ids {
@@ -172,3 +186,24 @@ pub fn star_two(input: &str) -> usize {
 
     particles.iter().filter(|x| x.is_some()).count()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn test_parse() {
+        let particles = parse("p=< 3,0,0>, v=< 2,0,0>, a=<-1,0,0>").unwrap();
+
+        assert_eq!(particles.len(), 1);
+        assert_eq!(particles[0].position.x, 3);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("p=<1,2>, v=<0,0,0>, a=<0,0,0>").is_err());
+        assert!(parse("p=<1,2,3>, v=<0,0,0>").is_err());
+        assert!(parse("p=<1,2,x>, v=<0,0,0>, a=<0,0,0>").is_err());
+        assert!(parse("p<1,2,3>, v=<0,0,0>, a=<0,0,0>").is_err());
+    }
+}
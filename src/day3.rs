@@ -1,20 +1,21 @@
-use grid::Grid;
-use std::iter;
-use std::ops::Add;
+use core::convert::TryFrom;
+use core::ops::Add;
+use grid::SparseGrid;
+use prelude::*;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-struct Point {
-    x: i32,
-    y: i32,
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl Point {
-    fn new(x: i32, y: i32) -> Self {
-        Point { x: x, y: y }
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
     }
 
-    fn manhattan_distance(&self, other: &Point) -> u32 {
-        ((self.x - other.x).abs() + (self.y - other.y).abs()) as u32
+    pub fn manhattan_distance(&self, other: &Point) -> u64 {
+        (self.x - other.x).unsigned_abs() + (self.y - other.y).unsigned_abs()
     }
 }
 
@@ -29,98 +30,202 @@ impl Add for Point {
     }
 }
 
-type GridDefinition = (u32, u32, u32);
-
-fn determine_min_grid_size(iloc: u32) -> GridDefinition {
-    let mut current = 1;
-    let mut level = 0;
+fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
 
-    while current * current < iloc {
-        current = current + 2;
-        level += 1;
+    let mut x = value;
+    let mut y = value / 2 + 1;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
     }
 
-    (current * current, current, level)
+    x
 }
 
-fn find_point(iloc: u32, grid: GridDefinition) -> Point {
-    let grid_side = grid.1;
-    if grid.2 == 0 {
+/// The location of `index` in the spiral, 1 is at the origin and 2 is to its
+/// right. Index 0 is treated as the origin as well.
+pub fn point_at(index: u64) -> Point {
+    if index <= 1 {
         return Point::new(0, 0);
     }
 
-    let mut location = Point::new(grid.2 as i32, -(grid.2 as i32));
-    let mut current_iloc = grid_side * grid_side;
+    // Ring `k` holds the indices in ((2k - 1)², (2k + 1)²] and ends in its
+    // bottom right corner, the sides are walked back from there.
+    let ring = isqrt(index - 1).div_ceil(2);
+    let side = i128::from(2 * ring);
+    let k = i128::from(ring);
+    let back = (2 * k + 1) * (2 * k + 1) - i128::from(index);
 
-    let steps = [
-        (grid_side - 1, Point::new(-1, 0)),
-        (grid_side - 1, Point::new(0, 1)),
-        (grid_side - 1, Point::new(1, 0)),
-        (grid_side - 1, Point::new(0, -1)),
-    ];
+    let (x, y) = if back <= side {
+        (k - back, -k)
+    } else if back <= 2 * side {
+        (-k, -k + back - side)
+    } else if back <= 3 * side {
+        (-k + back - 2 * side, k)
+    } else {
+        (k, k - back + 3 * side)
+    };
 
-    for step in steps.iter() {
-        for _ in 0..step.0 {
-            if iloc == current_iloc {
-                return location;
-            }
-            current_iloc -= 1;
-            location = location + step.1;
+    Point::new(x as i64, y as i64)
+}
+
+/// The index at `point`, or `None` if it does not fit in a `u64`.
+pub fn index_at(point: Point) -> Option<u64> {
+    let (x, y) = (i128::from(point.x), i128::from(point.y));
+    let k = x.abs().max(y.abs());
+    // Rings past this one start above u64::MAX.
+    if k > 1 << 32 {
+        return None;
+    }
+
+    let side = 2 * k;
+    let last = (2 * k + 1) * (2 * k + 1);
+
+    let index = if y == -k {
+        last - (k - x)
+    } else if x == -k {
+        last - side - (y + k)
+    } else if y == k {
+        last - 2 * side - (x + k)
+    } else {
+        last - 3 * side - (k - y)
+    };
+
+    u64::try_from(index).ok()
+}
+
+pub fn solve(index: u64) -> u64 {
+    point_at(index).manhattan_distance(&Point::new(0, 0))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// Only the cells sharing a side.
+    Four,
+    /// The cells sharing a side or a corner.
+    Eight,
+}
+
+impl Neighbourhood {
+    fn offsets(&self) -> &'static [(i64, i64)] {
+        match *self {
+            Neighbourhood::Four => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+            Neighbourhood::Eight => &[
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+            ],
         }
     }
+}
 
-    assert!(false, "Should not be here");
-    Point::new(0, 0)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Combine {
+    Sum,
+    Product,
 }
 
-pub fn solve(iloc: u32) -> u32 {
-    let origin = Point::new(0, 0);
-    let grid = determine_min_grid_size(iloc);
-    let loc = find_point(iloc, grid);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub neighbourhood: Neighbourhood,
+    pub combine: Combine,
+}
 
-    loc.manhattan_distance(&origin)
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            neighbourhood: Neighbourhood::Eight,
+            combine: Combine::Sum,
+        }
+    }
 }
 
-pub fn solve_star_two(target: u32) -> u32 {
-    let min_grid = determine_min_grid_size(target);
-    println!("Min grid: {:?}", min_grid);
-    let mut grid: Grid = Grid::new(min_grid.1 as usize);
-    grid[(0, 0)] = Some(1);
+/// The spiral where every cell combines the values of its already filled
+/// neighbours. Yields `(index, point, value)` starting at index 1 and ends
+/// only when a value no longer fits in a `u128`.
+pub struct NeighbourSpiral {
+    rule: Rule,
+    seed: u128,
+    grid: SparseGrid<u128>,
+    index: u64,
+}
 
-    let mut loc: (i32, i32) = (0, 0);
-    let mut current_side_length = 3;
-    let mut up_steps = 1;
+impl NeighbourSpiral {
+    pub fn new() -> Self {
+        NeighbourSpiral::with_rule(Rule::default(), 1)
+    }
+
+    pub fn with_rule(rule: Rule, seed: u128) -> Self {
+        NeighbourSpiral {
+            rule,
+            seed,
+            grid: SparseGrid::new(),
+            index: 1,
+        }
+    }
+}
+
+impl Default for NeighbourSpiral {
+    fn default() -> Self {
+        NeighbourSpiral::new()
+    }
+}
 
-    let up: (i32, i32) = (0, 1);
-    let left: (i32, i32) = (-1, 0);
-    let down = (0, -1);
-    let right = (1, 0);
+impl Iterator for NeighbourSpiral {
+    type Item = (u64, Point, u128);
 
-    loop {
-        let mut steps: Vec<(i32, i32)> = Vec::new();
-        steps.extend(iter::repeat(right).take(1));
-        steps.extend(iter::repeat(up).take(up_steps));
-        steps.extend(iter::repeat(left).take(current_side_length - 1));
-        steps.extend(iter::repeat(down).take(current_side_length - 1));
-        steps.extend(iter::repeat(right).take(current_side_length - 1));
+    fn next(&mut self) -> Option<Self::Item> {
+        let point = point_at(self.index);
+        let location = (point.x, point.y);
 
-        for step in steps.iter() {
-            loc = (loc.0 + step.0, loc.1 + step.1);
-            let value = grid.sum_of_neighbours(loc);
-            if value > target {
-                return value;
+        let value = if self.index == 1 {
+            Some(self.seed)
+        } else {
+            let mut neighbours = self
+                .grid
+                .neighbours(location, self.rule.neighbourhood.offsets());
+            match self.rule.combine {
+                Combine::Sum => neighbours.try_fold(0u128, |acc, &value| acc.checked_add(value)),
+                Combine::Product => {
+                    neighbours.try_fold(1u128, |acc, &value| acc.checked_mul(value))
+                }
             }
-            grid[loc] = Some(value);
-        }
+        }?;
 
-        current_side_length += 2;
-        up_steps += 2;
+        self.grid.insert(location, value);
+        self.index += 1;
+
+        Some((self.index - 1, point, value))
     }
 }
 
+/// The first value of the neighbour-sum spiral larger than `target`, an error
+/// when that value does not fit in a `u32`.
+pub fn solve_star_two(target: u32) -> Result<u32, String> {
+    let value = NeighbourSpiral::new()
+        .map(|(_, _, value)| value)
+        .find(|&value| value > u128::from(target))
+        .expect("Spiral values exceeded u128");
+
+    u32::try_from(value).map_err(|_| {
+        format!(
+            "The first spiral value above {} is {}, which does not fit in a u32",
+            target, value
+        )
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{determine_min_grid_size, find_point, solve, solve_star_two, Point};
+    use super::*;
 
     #[test]
     fn test_manhattan_distance() {
@@ -145,27 +250,111 @@ mod tests {
 
     #[test]
     fn test_cases_star_two() {
-        assert_eq!(solve_star_two(5), 10);
-        assert_eq!(solve_star_two(133), 142);
-        assert_eq!(solve_star_two(26), 54);
-        assert_eq!(solve_star_two(747), 806);
-        assert_eq!(solve_star_two(362), 747);
+        assert_eq!(solve_star_two(5), Ok(10));
+        assert_eq!(solve_star_two(133), Ok(142));
+        assert_eq!(solve_star_two(26), Ok(54));
+        assert_eq!(solve_star_two(747), Ok(806));
+        assert_eq!(solve_star_two(362), Ok(747));
+        assert!(solve_star_two(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_point_at() {
+        assert_eq!(point_at(1), Point::new(0, 0));
+        assert_eq!(point_at(3), Point::new(1, 1));
+        assert_eq!(point_at(12), Point::new(2, 1));
+        assert_eq!(point_at(25), Point::new(2, -2));
+        assert_eq!(point_at(26), Point::new(3, -2));
     }
 
     #[test]
-    fn test_determine_min_grid_size() {
-        assert_eq!(determine_min_grid_size(1), (1, 1, 0));
-        assert_eq!(determine_min_grid_size(2), (9, 3, 1));
-        assert_eq!(determine_min_grid_size(10), (25, 5, 2));
-        assert_eq!(determine_min_grid_size(25), (25, 5, 2));
-        assert_eq!(determine_min_grid_size(1024), (1089, 33, 16));
-        assert_eq!(determine_min_grid_size(312051), (312481, 559, 279));
+    fn test_against_walk() {
+        let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+        let mut point = Point::new(0, 0);
+        let mut index = 1;
+        let mut direction = 0;
+        let mut run = 1;
+
+        while index <= 1_000_000 {
+            for _ in 0..2 {
+                for _ in 0..run {
+                    assert_eq!(point_at(index), point);
+                    assert_eq!(index_at(point), Some(index));
+
+                    let (dx, dy) = directions[direction];
+                    point = Point::new(point.x + dx, point.y + dy);
+                    index += 1;
+                }
+                direction = (direction + 1) % 4;
+            }
+            run += 1;
+        }
     }
 
     #[test]
-    fn test_find_point() {
-        assert_eq!(find_point(1, (1, 1, 0)), Point::new(0, 0));
-        assert_eq!(find_point(3, (9, 3, 1)), Point::new(1, 1));
-        assert_eq!(find_point(12, (25, 5, 2)), Point::new(2, 1));
+    fn test_large_indices() {
+        for &index in &[
+            1 << 32,
+            (1 << 32) + 1,
+            1 << 40,
+            12_345_678_987_654_321,
+            u64::MAX,
+        ] {
+            assert_eq!(index_at(point_at(index)), Some(index));
+        }
+
+        assert_eq!(
+            point_at(u64::MAX),
+            Point::new(-2_147_483_646, 2_147_483_648)
+        );
+        assert_eq!(index_at(Point::new(i64::MAX, 0)), None);
+        assert_eq!(index_at(Point::new(i64::MIN, i64::MIN)), None);
+    }
+
+    #[test]
+    fn test_neighbour_spiral() {
+        let values = NeighbourSpiral::new()
+            .take(10)
+            .map(|(_, _, value)| value)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1, 1, 2, 4, 5, 10, 11, 23, 25, 26]);
+
+        let (index, point, value) = NeighbourSpiral::new().nth(22).unwrap();
+        assert_eq!((index, point, value), (23, Point::new(0, -2), 806));
+    }
+
+    #[test]
+    fn test_rules() {
+        let four = Rule {
+            neighbourhood: Neighbourhood::Four,
+            combine: Combine::Sum,
+        };
+        let values = NeighbourSpiral::with_rule(four, 1)
+            .take(10)
+            .map(|(_, _, value)| value)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1, 1, 1, 2, 2, 3, 3, 4, 5, 5]);
+
+        let product = Rule {
+            neighbourhood: Neighbourhood::Eight,
+            combine: Combine::Product,
+        };
+        let values = NeighbourSpiral::with_rule(product, 2)
+            .take(6)
+            .map(|(_, _, value)| value)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![2, 2, 4, 16, 32, 1024]);
+    }
+
+    #[test]
+    fn test_past_u32() {
+        let (index, _, value) = NeighbourSpiral::new()
+            .find(|&(_, _, value)| value > u128::from(u64::MAX))
+            .unwrap();
+        assert!(index > 100);
+        assert!(value > u128::from(u64::MAX));
+
+        let last = NeighbourSpiral::new().last().unwrap();
+        assert!(last.2 > u128::MAX / 10);
     }
 }
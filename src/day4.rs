@@ -1,9 +1,77 @@
-use std::collections::HashSet;
-use std::iter::FromIterator;
+use collections::Set;
+use core::fmt;
+use core::iter::FromIterator;
+use prelude::*;
+
+/// Why a phrase was rejected. `words` are the indices of the offending
+/// words within the phrase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub rule: String,
+    pub words: Vec<usize>,
+    /// The sorted letters shared by colliding anagrams.
+    pub canonical: Option<String>,
+}
+
+impl Rejection {
+    fn new(rule: &str, words: Vec<usize>) -> Self {
+        Rejection {
+            rule: rule.to_owned(),
+            words,
+            canonical: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Valid,
+    Invalid(Rejection),
+}
+
+impl Verdict {
+    pub fn is_valid(&self) -> bool {
+        *self == Verdict::Valid
+    }
+}
 
 pub trait Validator {
-    fn new() -> Self;
-    fn is_valid(&self, phrase: &Vec<&str>) -> bool;
+    fn new() -> Self
+    where
+        Self: Sized;
+    fn verdict(&self, phrase: &[&str]) -> Verdict;
+
+    fn is_valid(&self, phrase: &[&str]) -> bool {
+        self.verdict(phrase).is_valid()
+    }
+}
+
+/// The indices of every occurrence of the first key that appears twice.
+fn first_collision(keys: &[String]) -> Option<Vec<usize>> {
+    let mut seen: Set<&str> = Set::new();
+    let repeated = keys.iter().find(|key| !seen.insert(key.as_str()))?;
+
+    Some(
+        keys.iter()
+            .enumerate()
+            .filter(|&(_, key)| key == repeated)
+            .map(|(i, _)| i)
+            .collect(),
+    )
+}
+
+fn uniqueness_verdict(rule: &str, keys: &[String]) -> Verdict {
+    match first_collision(keys) {
+        Some(words) => Verdict::Invalid(Rejection::new(rule, words)),
+        None => Verdict::Valid,
+    }
+}
+
+fn canonical(word: &str) -> String {
+    let mut chars = word.chars().collect::<Vec<char>>();
+    chars.sort();
+
+    String::from_iter(chars)
 }
 
 pub struct UniquenessValidator {}
@@ -13,12 +81,13 @@ impl Validator for UniquenessValidator {
         UniquenessValidator {}
     }
 
-    fn is_valid(&self, phrase: &Vec<&str>) -> bool {
-        let len = &phrase.len();
-        let mut set: HashSet<&str> = HashSet::with_capacity(*len);
-        set.extend(phrase);
+    fn verdict(&self, phrase: &[&str]) -> Verdict {
+        let keys = phrase
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>();
 
-        set.len() == *len
+        uniqueness_verdict("unique", &keys)
     }
 }
 
@@ -29,20 +98,304 @@ impl Validator for AnagramValidator {
         AnagramValidator {}
     }
 
-    fn is_valid(&self, phrase: &Vec<&str>) -> bool {
-        let sorted = phrase.iter().map(|&word| {
-            let mut chars = word.chars().collect::<Vec<char>>();
-            chars.sort_by(|a, b| b.cmp(a));
+    fn verdict(&self, phrase: &[&str]) -> Verdict {
+        let sorted = phrase
+            .iter()
+            .map(|word| canonical(word))
+            .collect::<Vec<_>>();
 
-            String::from_iter(chars)
-        });
+        match uniqueness_verdict("no-anagrams", &sorted) {
+            Verdict::Invalid(mut rejection) => {
+                rejection.canonical = Some(sorted[rejection.words[0]].clone());
+                Verdict::Invalid(rejection)
+            }
+            Verdict::Valid => Verdict::Valid,
+        }
+    }
+}
+
+/// A passphrase policy assembled at runtime, see `Rule::parse` for the rule
+/// string syntax.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    Unique,
+    UniqueIgnoreCase,
+    NoAnagrams,
+    MinWords(usize),
+    MaxWords(usize),
+    /// Every pair of words is at least this many edits apart.
+    MinDistance(usize),
+    /// No word is in the list.
+    Deny(Vec<String>),
+    /// Every word is in the list.
+    Dictionary(Vec<String>),
+    All(Vec<Rule>),
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+}
 
-        let len = sorted.len();
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
 
-        let mut set: HashSet<String> = HashSet::with_capacity(len);
-        set.extend(sorted);
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
 
-        set.len() == len
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+impl Validator for Rule {
+    /// The empty policy, it accepts every phrase.
+    fn new() -> Self {
+        Rule::All(Vec::new())
+    }
+
+    fn verdict(&self, phrase: &[&str]) -> Verdict {
+        let reject = |words: Vec<usize>| Verdict::Invalid(Rejection::new(&self.to_string(), words));
+        let check = |valid: bool| {
+            if valid {
+                Verdict::Valid
+            } else {
+                reject(Vec::new())
+            }
+        };
+        let offending = |matches: &dyn Fn(&str) -> bool| {
+            let words = (0..phrase.len())
+                .filter(|&i| matches(phrase[i]))
+                .collect::<Vec<_>>();
+
+            if words.is_empty() {
+                Verdict::Valid
+            } else {
+                reject(words)
+            }
+        };
+
+        match *self {
+            Rule::Unique => UniquenessValidator::new().verdict(phrase),
+            Rule::UniqueIgnoreCase => {
+                let lowercase = phrase
+                    .iter()
+                    .map(|word| word.to_lowercase())
+                    .collect::<Vec<_>>();
+
+                uniqueness_verdict("unique-ignore-case", &lowercase)
+            }
+            Rule::NoAnagrams => AnagramValidator::new().verdict(phrase),
+            Rule::MinWords(count) => check(phrase.len() >= count),
+            Rule::MaxWords(count) => check(phrase.len() <= count),
+            Rule::MinDistance(distance) => {
+                let close = (0..phrase.len())
+                    .flat_map(|i| (i + 1..phrase.len()).map(move |j| (i, j)))
+                    .find(|&(i, j)| edit_distance(phrase[i], phrase[j]) < distance);
+
+                match close {
+                    Some((i, j)) => reject(vec![i, j]),
+                    None => Verdict::Valid,
+                }
+            }
+            Rule::Deny(ref words) => offending(&|word| words.iter().any(|denied| denied == word)),
+            Rule::Dictionary(ref words) => {
+                offending(&|word| !words.iter().any(|known| known == word))
+            }
+            Rule::All(ref rules) => rules
+                .iter()
+                .map(|rule| rule.verdict(phrase))
+                .find(|verdict| !verdict.is_valid())
+                .unwrap_or(Verdict::Valid),
+            Rule::Any(ref rules) => check(rules.iter().any(|rule| rule.is_valid(phrase))),
+            Rule::Not(ref rule) => check(!rule.is_valid(phrase)),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |rules: &Vec<Rule>| {
+            rules
+                .iter()
+                .map(|rule| rule.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match *self {
+            Rule::Unique => write!(f, "unique"),
+            Rule::UniqueIgnoreCase => write!(f, "unique-ignore-case"),
+            Rule::NoAnagrams => write!(f, "no-anagrams"),
+            Rule::MinWords(count) => write!(f, "min-words({})", count),
+            Rule::MaxWords(count) => write!(f, "max-words({})", count),
+            Rule::MinDistance(distance) => write!(f, "min-distance({})", distance),
+            Rule::Deny(ref words) => write!(f, "deny({})", words.join(", ")),
+            Rule::Dictionary(ref words) => write!(f, "dictionary({})", words.join(", ")),
+            Rule::All(ref rules) => write!(f, "all({})", join(rules)),
+            Rule::Any(ref rules) => write!(f, "any({})", join(rules)),
+            Rule::Not(ref rule) => write!(f, "not({})", rule),
+        }
+    }
+}
+
+/// Deeper rules are rejected rather than risking the stack.
+const MAX_RULE_DEPTH: usize = 64;
+
+struct RuleParser<'a> {
+    input: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("Expected {} at position {}", expected, self.position)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", c)))
+        }
+    }
+
+    fn token(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+            .unwrap_or(rest.len());
+        self.position += len;
+
+        &rest[..len]
+    }
+
+    fn list<T, F>(&mut self, mut item: F) -> Result<Vec<T>, String>
+    where
+        F: FnMut(&mut Self) -> Result<T, String>,
+    {
+        self.expect('(')?;
+        let mut items = Vec::new();
+        if self.eat(')') {
+            return Ok(items);
+        }
+
+        loop {
+            items.push(item(self)?);
+            if self.eat(')') {
+                return Ok(items);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let mut numbers = self.list(|parser| {
+            let position = parser.position;
+            parser
+                .token()
+                .parse::<usize>()
+                .map_err(|_| format!("Expected a number at position {}", position))
+        })?;
+
+        match numbers.len() {
+            1 => Ok(numbers.remove(0)),
+            _ => Err(self.error("exactly one number")),
+        }
+    }
+
+    fn words(&mut self) -> Result<Vec<String>, String> {
+        self.list(|parser| match parser.token() {
+            "" => Err(parser.error("a word")),
+            word => Ok(word.to_owned()),
+        })
+    }
+
+    fn rule(&mut self) -> Result<Rule, String> {
+        self.depth += 1;
+        if self.depth > MAX_RULE_DEPTH {
+            return Err(format!(
+                "Rule nested too deeply at position {}",
+                self.position
+            ));
+        }
+
+        let rule = self.rule_body();
+        self.depth -= 1;
+
+        rule
+    }
+
+    fn rule_body(&mut self) -> Result<Rule, String> {
+        self.skip_whitespace();
+        let position = self.position;
+
+        match self.token() {
+            "unique" => Ok(Rule::Unique),
+            "unique-ignore-case" => Ok(Rule::UniqueIgnoreCase),
+            "no-anagrams" => Ok(Rule::NoAnagrams),
+            "min-words" => self.number().map(Rule::MinWords),
+            "max-words" => self.number().map(Rule::MaxWords),
+            "min-distance" => self.number().map(Rule::MinDistance),
+            "deny" => self.words().map(Rule::Deny),
+            "dictionary" => self.words().map(Rule::Dictionary),
+            "all" => self.list(|parser| parser.rule()).map(Rule::All),
+            "any" => self.list(|parser| parser.rule()).map(Rule::Any),
+            "not" => {
+                self.expect('(')?;
+                let rule = self.rule()?;
+                self.expect(')')?;
+
+                Ok(Rule::Not(Box::new(rule)))
+            }
+            "" => Err(self.error("a rule")),
+            name => Err(format!("Unknown rule {:?} at position {}", name, position)),
+        }
+    }
+}
+
+impl Rule {
+    /// Parses a rule string such as
+    /// `all(unique-ignore-case, min-words(3), not(any(deny(password), max-words(4))))`.
+    pub fn parse(input: &str) -> Result<Rule, String> {
+        let mut parser = RuleParser {
+            input,
+            position: 0,
+            depth: 0,
+        };
+        let rule = parser.rule()?;
+
+        parser.skip_whitespace();
+        if parser.position != input.len() {
+            return Err(parser.error("end of rule"));
+        }
+
+        Ok(rule)
     }
 }
 
@@ -56,8 +409,74 @@ pub fn parse(input: &str) -> Vec<Vec<&str>> {
 }
 
 pub fn solve<T: Validator>(input: &str) -> u32 {
+    solve_with(input, &T::new())
+}
+
+pub struct LineReport {
+    pub line: usize,
+    pub words: Vec<String>,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for LineReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rejection = match self.verdict {
+            Verdict::Valid => return write!(f, "{}: valid", self.line),
+            Verdict::Invalid(ref rejection) => rejection,
+        };
+
+        write!(f, "{}: rejected by {}", self.line, rejection.rule)?;
+        if !rejection.words.is_empty() {
+            let words = rejection
+                .words
+                .iter()
+                .map(|&i| format!("{} ({})", i, self.words[i]))
+                .collect::<Vec<_>>();
+            write!(f, ", words {}", words.join(", "))?;
+        }
+        if let Some(ref canonical) = rejection.canonical {
+            write!(f, " share the letters {}", canonical)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Report {
+    pub valid: u32,
+    pub lines: Vec<LineReport>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        write!(f, "{} of {} valid", self.valid, self.lines.len())
+    }
+}
+
+/// Validates every phrase and explains the rejected ones, lines are 1-based.
+pub fn report(input: &str, validator: &dyn Validator) -> Report {
+    let lines = parse(input)
+        .iter()
+        .enumerate()
+        .map(|(i, phrase)| LineReport {
+            line: i + 1,
+            words: phrase.iter().map(|word| word.to_string()).collect(),
+            verdict: validator.verdict(phrase),
+        })
+        .collect::<Vec<_>>();
+
+    Report {
+        valid: lines.iter().filter(|line| line.verdict.is_valid()).count() as u32,
+        lines,
+    }
+}
+
+pub fn solve_with(input: &str, validator: &dyn Validator) -> u32 {
     let phrases = parse(input);
-    let validator = T::new();
 
     phrases
         .iter()
@@ -69,7 +488,7 @@ pub fn solve<T: Validator>(input: &str) -> u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{solve, AnagramValidator, UniquenessValidator};
+    use super::*;
 
     #[test]
     fn test_cases_star_one() {
@@ -92,4 +511,136 @@ mod tests {
             ";
         assert_eq!(solve::<AnagramValidator>(input), 3);
     }
+
+    fn is_valid(rule: &Rule, phrase: &str) -> bool {
+        rule.is_valid(&phrase.split_whitespace().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+    }
+
+    #[test]
+    fn test_rules() {
+        assert!(!is_valid(&Rule::UniqueIgnoreCase, "aa AA bb"));
+        assert!(is_valid(&Rule::Unique, "aa AA bb"));
+        assert!(!is_valid(&Rule::MinWords(4), "aa bb cc"));
+        assert!(is_valid(&Rule::MaxWords(3), "aa bb cc"));
+        assert!(!is_valid(&Rule::MinDistance(2), "abc abd xyz"));
+        assert!(is_valid(&Rule::MinDistance(2), "abc bca xyz"));
+        assert!(!is_valid(
+            &Rule::Deny(vec!["hunter2".to_owned()]),
+            "my hunter2"
+        ));
+        assert!(!is_valid(&Rule::Dictionary(vec!["aa".to_owned()]), "aa bb"));
+        assert!(is_valid(&Rule::new(), "aa aa"));
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = Rule::parse("all(unique, not(any(min-words(5), dictionary(x, y))))").unwrap();
+        assert_eq!(
+            rule,
+            Rule::All(vec![
+                Rule::Unique,
+                Rule::Not(Box::new(Rule::Any(vec![
+                    Rule::MinWords(5),
+                    Rule::Dictionary(vec!["x".to_owned(), "y".to_owned()]),
+                ]))),
+            ])
+        );
+        assert_eq!(
+            rule.to_string(),
+            "all(unique, not(any(min-words(5), dictionary(x, y))))"
+        );
+        assert!(is_valid(&rule, "a b c"));
+        assert!(!is_valid(&rule, "x y"));
+        assert!(!is_valid(&rule, "a b c d e"));
+
+        assert_eq!(
+            Rule::parse("all(unique,"),
+            Err("Expected a rule at position 11".to_owned())
+        );
+        assert_eq!(
+            Rule::parse("all(uniq)"),
+            Err("Unknown rule \"uniq\" at position 4".to_owned())
+        );
+        assert!(Rule::parse("min-words(x)").is_err());
+        assert!(Rule::parse("min-words(1, 2)").is_err());
+        assert!(Rule::parse("not(unique, unique)").is_err());
+        assert!(Rule::parse("unique unique").is_err());
+        assert!(Rule::parse("").is_err());
+        assert!(Rule::parse(&format!("{}unique{}", "not(".repeat(100), ")".repeat(100))).is_err());
+    }
+
+    #[test]
+    fn test_solve_with_rule() {
+        let input = "
+            aa bb cc dd ee
+            aa bb cc dd aa
+            aa bb cc dd aaa
+            ";
+        let rule = Rule::parse("all(unique, max-words(4))").unwrap();
+
+        assert_eq!(solve_with(input, &Rule::Unique), 2);
+        assert_eq!(solve_with(input, &rule), 0);
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let phrase = vec!["abcde", "xyz", "ecdab", "xyz"];
+
+        assert_eq!(
+            UniquenessValidator::new().verdict(&phrase),
+            Verdict::Invalid(Rejection {
+                rule: "unique".to_owned(),
+                words: vec![1, 3],
+                canonical: None,
+            })
+        );
+        assert_eq!(
+            AnagramValidator::new().verdict(&phrase),
+            Verdict::Invalid(Rejection {
+                rule: "no-anagrams".to_owned(),
+                words: vec![0, 2],
+                canonical: Some("abcde".to_owned()),
+            })
+        );
+
+        let rule = Rule::parse("all(max-words(4), min-distance(3), deny(xyz))").unwrap();
+        assert_eq!(
+            rule.verdict(&vec!["abc", "abd", "xyz"]),
+            Verdict::Invalid(Rejection::new("min-distance(3)", vec![0, 1]))
+        );
+        assert_eq!(
+            rule.verdict(&vec!["abc", "xyz"]),
+            Verdict::Invalid(Rejection::new("deny(xyz)", vec![1]))
+        );
+        assert_eq!(
+            rule.verdict(&vec!["a", "b", "c", "d", "e"]),
+            Verdict::Invalid(Rejection::new("max-words(4)", vec![]))
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let input = "
+            abcde fghij
+            abcde xyz ecdab
+            oiii ioii iioi iiio
+            ";
+        let report = report(input, &AnagramValidator::new());
+
+        assert_eq!(report.valid, 1);
+        assert_eq!(
+            report.to_string(),
+            "1: valid
+2: rejected by no-anagrams, words 0 (abcde), 2 (ecdab) share the letters abcde
+3: rejected by no-anagrams, words 0 (oiii), 1 (ioii), 2 (iioi), 3 (iiio) share the letters iiio
+1 of 3 valid"
+        );
+    }
 }
@@ -1,37 +1,551 @@
-pub fn solve<F>(input: &str, alter: F) -> u32
+use core::fmt;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
+
+pub fn parse<T: Integer>(input: &str) -> Result<Vec<T>, String> {
+    input
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<T>()
+                .map_err(|_| format!("Expected only numbers found {:?}", v))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MazeError {
+    Parse(String),
+    Arithmetic(ArithmeticError),
+    /// The maze returned to an earlier state and will never exit.
+    Loop {
+        steps: u64,
+        cycle: u64,
+    },
+}
+
+impl From<ArithmeticError> for MazeError {
+    fn from(error: ArithmeticError) -> Self {
+        MazeError::Arithmetic(error)
+    }
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MazeError::Parse(ref error) => write!(f, "{}", error),
+            MazeError::Arithmetic(error) => write!(f, "{}", error),
+            MazeError::Loop { steps, cycle } => write!(
+                f,
+                "Maze never exits, after {} steps the state repeats every {} steps",
+                steps, cycle
+            ),
+        }
+    }
+}
+
+/// One executed jump, `offset` is the value at `ip` before it was altered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Step<T> {
+    pub ip: usize,
+    pub offset: T,
+}
+
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    value ^ (value >> 31)
+}
+
+fn cell_hash<T: Integer>(index: usize, value: T) -> u64 {
+    let value = value.to_i128();
+
+    mix(mix(index as u64) ^ value as u64 ^ mix((value >> 64) as u64))
+}
+
+fn chunk_hash(chunk: usize, bits: u16) -> u64 {
+    mix(!(chunk as u64) ^ mix(u64::from(bits)))
+}
+
+/// Brent's cycle detection over maze states. States are compared by an
+/// incrementally maintained hash first and in full only when that matches.
+struct Detector<T> {
+    ip: T,
+    hash: u64,
+    jumps: Vec<T>,
+    chunks: Vec<u16>,
+    power: u64,
+    length: u64,
+}
+
+impl<T: Integer> Detector<T> {
+    fn new(ip: T, hash: u64, jumps: &[T], chunks: &[u16]) -> Self {
+        Detector {
+            ip,
+            hash,
+            jumps: jumps.to_vec(),
+            chunks: chunks.to_vec(),
+            power: 1,
+            length: 0,
+        }
+    }
+
+    /// Returns the cycle length if this state was seen before.
+    fn observe(&mut self, ip: T, hash: u64, jumps: &[T], chunks: &[u16]) -> Option<u64> {
+        self.length += 1;
+        if hash == self.hash
+            && ip == self.ip
+            && jumps == &self.jumps[..]
+            && chunks == &self.chunks[..]
+        {
+            return Some(self.length);
+        }
+
+        if self.length == self.power {
+            *self = Detector {
+                power: self.power * 2,
+                ..Detector::new(ip, hash, jumps, chunks)
+            };
+        }
+
+        None
+    }
+}
+
+/// Cells per fast-forward chunk, the stable cells of a chunk are packed into
+/// the bits of a `u16`.
+const CHUNK: usize = 16;
+
+/// For every chunk state and entry offset, the chunk state after jumping
+/// through it, the offset it is left at and the number of jumps taken.
+fn chunk_table() -> Vec<u32> {
+    let mut table = Vec::with_capacity(CHUNK << 16);
+
+    for bits in 0..=u16::MAX {
+        for entry in 0..CHUNK {
+            let (mut bits, mut position, mut steps) = (bits, entry, 0u32);
+            while position < CHUNK {
+                let jump = 2 + ((bits >> position) & 1) as usize;
+                bits ^= 1 << position;
+                position += jump;
+                steps += 1;
+            }
+
+            table.push(u32::from(bits) | (position as u32) << 16 | steps << 24);
+        }
+    }
+
+    table
+}
+
+/// The `chunk_table`, built by the first `run_fast` that needs it and shared
+/// by every maze after that.
+#[cfg(feature = "std")]
+fn shared_chunk_table() -> &'static [u32] {
+    use std::sync::OnceLock;
+
+    static TABLE: OnceLock<Vec<u32>> = OnceLock::new();
+    TABLE.get_or_init(chunk_table)
+}
+
+/// The day 5 machine, a list of jump offsets where every jump alters the
+/// offset it came from.
+pub struct JumpMaze<T, F> {
+    jumps: Vec<T>,
+    alter: F,
+    ip: T,
+    steps: u64,
+    dispatches: u64,
+    hash: u64,
+    detector: Detector<T>,
+    trace: Option<Vec<Step<T>>>,
+    /// Without `std` there is no lock to share one `chunk_table` between
+    /// mazes, so each maze builds its own on the first `run_fast` that needs
+    /// it.
+    #[cfg(not(feature = "std"))]
+    table: Vec<u32>,
+}
+
+impl<T, F> JumpMaze<T, F>
 where
-    F: Fn(i32) -> i32,
+    T: Integer,
+    F: Fn(T) -> T,
 {
-    let mut jumps: Vec<i32> = input
-        .split_whitespace()
-        .map(|v| v.parse::<i32>().unwrap())
-        .collect();
-    // Instruction pointer
-    let mut ip: i32 = 0;
-    let mut steps = 0;
-    let len = jumps.len() as i32;
+    pub fn new(jumps: Vec<T>, alter: F) -> Self {
+        let hash = jumps.iter().enumerate().fold(0u64, |acc, (i, &value)| {
+            acc.wrapping_add(cell_hash(i, value))
+        });
+
+        JumpMaze {
+            detector: Detector::new(T::zero(), hash, &jumps, &[]),
+            jumps,
+            alter,
+            ip: T::zero(),
+            steps: 0,
+            dispatches: 0,
+            hash,
+            trace: None,
+            #[cfg(not(feature = "std"))]
+            table: Vec::new(),
+        }
+    }
+
+    pub fn parse(input: &str, alter: F) -> Result<Self, String> {
+        parse(input).map(|jumps| JumpMaze::new(jumps, alter))
+    }
+
+    /// Records every following step in `trace`.
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    pub fn ip(&self) -> T {
+        self.ip
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The number of single jumps and chunk lookups executed. Equal to
+    /// `steps` unless `run_fast` crossed chunks in one lookup.
+    pub fn dispatches(&self) -> u64 {
+        self.dispatches
+    }
+
+    pub fn offsets(&self) -> &[T] {
+        &self.jumps
+    }
+
+    pub fn trace(&self) -> &[Step<T>] {
+        self.trace.as_ref().map_or(&[], |trace| &trace[..])
+    }
+
+    fn position(&self) -> Option<usize> {
+        self.ip.to_usize().filter(|&ip| ip < self.jumps.len())
+    }
+
+    pub fn exited(&self) -> bool {
+        self.position().is_none()
+    }
+
+    fn jump(&mut self, ip: usize) -> Result<Step<T>, ArithmeticError> {
+        let offset = self.jumps[ip];
+        let altered = offset.try_add((self.alter)(offset))?;
+        let target = self.ip.try_add(offset)?;
+        let steps = self.steps.try_add(1)?;
+
+        self.jumps[ip] = altered;
+        self.hash = self
+            .hash
+            .wrapping_sub(cell_hash(ip, offset))
+            .wrapping_add(cell_hash(ip, altered));
+        self.ip = target;
+        self.steps = steps;
+        self.dispatches += 1;
+
+        Ok(Step { ip, offset })
+    }
+
+    /// Executes one jump, `None` once the maze has been exited.
+    pub fn step(&mut self) -> Result<Option<Step<T>>, MazeError> {
+        let ip = match self.position() {
+            Some(ip) => ip,
+            None => return Ok(None),
+        };
+
+        let step = self.jump(ip)?;
+        if let Some(ref mut trace) = self.trace {
+            trace.push(step);
+        }
+        if let Some(cycle) = self.detector.observe(self.ip, self.hash, &self.jumps, &[]) {
+            return Err(MazeError::Loop {
+                steps: self.steps,
+                cycle,
+            });
+        }
+
+        Ok(Some(step))
+    }
+
+    /// Executes up to `count` jumps and returns how many were taken.
+    pub fn run_n(&mut self, count: u64) -> Result<u64, MazeError> {
+        let start = self.steps;
+        for _ in 0..count {
+            if self.step()?.is_none() {
+                break;
+            }
+        }
+
+        Ok(self.steps - start)
+    }
+
+    /// Runs until the maze is exited and returns the total number of steps.
+    pub fn run(&mut self) -> Result<u64, MazeError> {
+        while self.step()?.is_some() {}
+
+        Ok(self.steps)
+    }
+
+    fn value(value: usize) -> Option<T> {
+        T::from_usize(value)
+    }
+
+    /// Whether the offsets 2 and 3 turn into each other when jumped from, in
+    /// which case a stretch of them is only ever crossed forwards.
+    fn toggles(&self) -> bool {
+        match (Self::value(2), Self::value(3)) {
+            (Some(two), Some(three)) => {
+                two.try_add((self.alter)(two)) == Ok(three)
+                    && three.try_add((self.alter)(three)) == Ok(two)
+            }
+            _ => false,
+        }
+    }
+
+    fn freeze_chunk(&mut self, chunks: &mut Vec<u16>, three: T) {
+        let start = chunks.len() * CHUNK;
+        let mut bits = 0;
+        for k in 0..CHUNK {
+            let value = self.jumps[start + k];
+            self.hash = self.hash.wrapping_sub(cell_hash(start + k, value));
+            bits |= u16::from(value == three) << k;
+        }
+
+        self.hash = self.hash.wrapping_add(chunk_hash(chunks.len(), bits));
+        chunks.push(bits);
+    }
+
+    fn thaw_chunks(&mut self, chunks: &[u16], two: T, three: T) {
+        for (chunk, &bits) in chunks.iter().enumerate() {
+            self.hash = self.hash.wrapping_sub(chunk_hash(chunk, bits));
+            for k in 0..CHUNK {
+                let i = chunk * CHUNK + k;
+                self.jumps[i] = if bits & (1 << k) != 0 { three } else { two };
+                self.hash = self.hash.wrapping_add(cell_hash(i, self.jumps[i]));
+            }
+        }
+    }
+
+    fn fast_forward(
+        &mut self,
+        table: &[u32],
+        chunks: &mut Vec<u16>,
+        two: T,
+        three: T,
+    ) -> Result<u64, MazeError> {
+        let mut stable = 0;
+
+        loop {
+            while stable < self.jumps.len()
+                && (self.jumps[stable] == two || self.jumps[stable] == three)
+            {
+                stable += 1;
+                if stable % CHUNK == 0 {
+                    self.freeze_chunk(chunks, three);
+                }
+            }
+
+            let ip = match self.position() {
+                Some(ip) => ip,
+                None => return Ok(self.steps),
+            };
+
+            if ip < chunks.len() * CHUNK {
+                let chunk = ip / CHUNK;
+                let entry = table[chunks[chunk] as usize * CHUNK + ip % CHUNK];
+                let bits = entry as u16;
+                let exit = chunk * CHUNK + ((entry >> 16) & 0xff) as usize;
+
+                self.hash = self
+                    .hash
+                    .wrapping_sub(chunk_hash(chunk, chunks[chunk]))
+                    .wrapping_add(chunk_hash(chunk, bits));
+                chunks[chunk] = bits;
+                self.ip = Self::value(exit).ok_or(ArithmeticError::Overflow)?;
+                self.steps = self.steps.try_add(u64::from(entry >> 24))?;
+                self.dispatches += 1;
+                continue;
+            }
+
+            self.jump(ip)?;
+            if let Some(cycle) = self
+                .detector
+                .observe(self.ip, self.hash, &self.jumps, chunks)
+            {
+                return Err(MazeError::Loop {
+                    steps: self.steps,
+                    cycle,
+                });
+            }
+        }
+    }
+
+    /// Like `run` but crosses the leading stretch of offsets that are all 2
+    /// or 3 a chunk at a time through a lookup table. Falls back to `run` when
+    /// tracing or when the alter rule does not swap 2 and 3.
+    pub fn run_fast(&mut self) -> Result<u64, MazeError> {
+        if self.trace.is_some() || !self.toggles() {
+            return self.run();
+        }
 
-    while ip >= 0 && ip < len {
-        let instruction = jumps[ip as usize];
-        jumps[ip as usize] += alter(instruction);
-        ip += instruction;
-        steps += 1;
+        #[cfg(feature = "std")]
+        let table = shared_chunk_table();
+        #[cfg(not(feature = "std"))]
+        let owned = {
+            if self.table.is_empty() {
+                self.table = chunk_table();
+            }
+            core::mem::take(&mut self.table)
+        };
+        #[cfg(not(feature = "std"))]
+        let table = &owned[..];
+
+        let (two, three) = (Self::value(2).unwrap(), Self::value(3).unwrap());
+        let mut chunks = Vec::new();
+        self.detector = Detector::new(self.ip, self.hash, &self.jumps, &chunks);
+
+        let result = self.fast_forward(table, &mut chunks, two, three);
+        #[cfg(not(feature = "std"))]
+        {
+            self.table = owned;
+        }
+        self.thaw_chunks(&chunks, two, three);
+        self.detector = Detector::new(self.ip, self.hash, &self.jumps, &[]);
+
+        result
     }
+}
+
+pub fn solve_checked<T, F>(input: &str, alter: F) -> Result<u64, MazeError>
+where
+    T: Integer,
+    F: Fn(T) -> T,
+{
+    JumpMaze::<T, F>::parse(input, alter)
+        .map_err(MazeError::Parse)?
+        .run_fast()
+}
 
-    steps
+pub fn solve<F>(input: &str, alter: F) -> Result<u64, MazeError>
+where
+    F: Fn(i32) -> i32,
+{
+    solve_checked::<i32, F>(input, alter)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
+
+    fn part_two(offset: i32) -> i32 {
+        if offset >= 3 {
+            -1
+        } else {
+            1
+        }
+    }
 
     #[test]
     fn test_cases_star_one() {
-        assert_eq!(solve("0 3 0 1 -3", |_| 1), 5);
+        assert_eq!(solve("0 3 0 1 -3", |_| 1), Ok(5));
     }
 
     #[test]
     fn test_cases_star_two() {
-        assert_eq!(solve("0 3 0 1 -3", |i| if i >= 3 { -1 } else { 1 }), 10);
+        assert_eq!(solve("0 3 0 1 -3", |i| if i >= 3 { -1 } else { 1 }), Ok(10));
+        assert_eq!(
+            solve("0 x", |_| 1),
+            Err(MazeError::Parse(
+                "Expected only numbers found \"x\"".to_owned()
+            ))
+        );
+        let error = solve("0", |_| 0).unwrap_err();
+        assert_eq!(error, MazeError::Loop { steps: 1, cycle: 1 });
+        assert_eq!(
+            error.to_string(),
+            "Maze never exits, after 1 steps the state repeats every 1 steps"
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(
+            solve_checked::<i32, _>("2147483647 0", |_| 1),
+            Err(MazeError::Arithmetic(ArithmeticError::Overflow))
+        );
+        assert_eq!(solve_checked::<i64, _>("2147483647 0", |_| 1), Ok(1));
+        assert_eq!(
+            solve_checked::<i64, _>("0 x", |_| 1),
+            Err(MazeError::Parse(
+                "Expected only numbers found \"x\"".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse::<i32>("0 3 x").is_err());
+        assert!(parse::<i32>("99999999999").is_err());
+        assert_eq!(parse::<i64>("99999999999"), Ok(vec![99999999999]));
+    }
+
+    #[test]
+    fn test_step_and_trace() {
+        let mut maze = JumpMaze::parse("0 3 0 1 -3", |_: i32| 1)
+            .unwrap()
+            .with_trace();
+
+        assert_eq!(maze.step(), Ok(Some(Step { ip: 0, offset: 0 })));
+        assert_eq!(maze.run_n(2), Ok(2));
+        assert_eq!(maze.offsets(), &[2, 4, 0, 1, -3]);
+        assert_eq!(maze.ip(), 4);
+        assert_eq!(maze.run(), Ok(5));
+        assert!(maze.exited());
+        assert_eq!(maze.step(), Ok(None));
+        assert_eq!(
+            maze.trace().iter().map(|step| step.ip).collect::<Vec<_>>(),
+            vec![0, 0, 1, 4, 1]
+        );
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        let mut maze = JumpMaze::new(vec![1, -1], |_: i32| 0);
+        assert_eq!(maze.run(), Err(MazeError::Loop { steps: 3, cycle: 2 }));
+
+        let mut jumps = vec![2; 32];
+        jumps.extend(vec![0, 0, 0]);
+        let mut maze = JumpMaze::new(jumps, |offset: i32| match offset {
+            2 => 1,
+            3 => -1,
+            _ => 0,
+        });
+        match maze.run_fast() {
+            Err(MazeError::Loop { cycle, .. }) => assert_eq!(cycle, 1),
+            other => panic!("Expected a loop, got {:?}", other),
+        }
+        assert_eq!(maze.offsets()[..4], [3, 2, 3, 2]);
+    }
+
+    #[test]
+    fn test_fast_forward_matches_run() {
+        let mut seed = 2017u64;
+        for _ in 0..50 {
+            let jumps = (0..200)
+                .map(|_| {
+                    seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                    ((seed >> 33) % 9) as i32 - 3
+                })
+                .collect::<Vec<_>>();
+
+            let mut slow = JumpMaze::new(jumps.clone(), part_two);
+            let mut fast = JumpMaze::new(jumps, part_two);
+
+            assert_eq!(fast.run_fast(), slow.run());
+            assert_eq!(fast.offsets(), slow.offsets());
+            assert_eq!(fast.ip(), slow.ip());
+        }
     }
 }
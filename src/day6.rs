@@ -1,71 +1,458 @@
-use std::collections::HashMap;
+use collections::Map;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
 
-fn parse(input: &str) -> Vec<u32> {
-    input
+pub fn parse(input: &str) -> Result<Vec<u32>, String> {
+    let banks = input
         .split_whitespace()
-        .map(|v| v.parse::<u32>().expect("Expected only numbers"))
-        .collect()
+        .map(|v| {
+            v.parse::<u32>()
+                .map_err(|_| format!("Expected only numbers found {:?}", v))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if banks.is_empty() {
+        return Err("Expected at least one memory bank".to_owned());
+    }
+
+    Ok(banks)
+}
+
+/// How a redistribution cycle picks a bank and spreads its blocks.
+pub trait Redistribution {
+    /// The index of the bank to empty.
+    fn select(&self, banks: &[u32]) -> usize;
+    /// Hands out `blocks` taken from the bank at `from`, which is already
+    /// empty.
+    fn spread(&self, banks: &mut [u32], from: usize, blocks: u32);
+
+    /// Does nothing without banks, so no banks are a cycle of length one.
+    fn redistribute(&self, banks: &mut [u32]) {
+        if banks.is_empty() {
+            return;
+        }
+
+        let from = self.select(banks);
+        let blocks = banks[from];
+
+        banks[from] = 0;
+        self.spread(banks, from, blocks);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tie {
+    LowestIndex,
+    HighestIndex,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Right,
+    Left,
+}
+
+/// Where the blocks that do not divide evenly over all banks go.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Remainder {
+    /// One each to the banks following the emptied one.
+    Following,
+    /// All of them to the first bank following the emptied one.
+    First,
 }
 
-fn identify_next(values: &Vec<u32>) -> (usize, u32) {
-    let mut max = 0;
-    let mut index = 0;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Policy {
+    pub tie: Tie,
+    pub direction: Direction,
+    pub remainder: Remainder,
+}
 
-    for (i, &v) in values.iter().enumerate() {
-        if v > max {
-            max = v;
-            index = i;
+impl Default for Policy {
+    /// The puzzle's policy, the lowest index wins and blocks go to the right
+    /// one at a time.
+    fn default() -> Self {
+        Policy {
+            tie: Tie::LowestIndex,
+            direction: Direction::Right,
+            remainder: Remainder::Following,
         }
     }
+}
 
-    (index, max)
+impl Policy {
+    fn neighbour(&self, len: usize, from: usize, distance: usize) -> usize {
+        match self.direction {
+            Direction::Right => (from + distance) % len,
+            Direction::Left => (from + len - distance % len) % len,
+        }
+    }
 }
 
-pub fn solve(input: &str) -> (u32, u32) {
-    let parsed = parse(input);
+impl Redistribution for Policy {
+    fn select(&self, banks: &[u32]) -> usize {
+        let mut index = 0;
+
+        for (i, &v) in banks.iter().enumerate() {
+            let wins = match self.tie {
+                Tie::LowestIndex => v > banks[index],
+                Tie::HighestIndex => v >= banks[index],
+            };
+            if wins {
+                index = i;
+            }
+        }
+
+        index
+    }
+
+    fn spread(&self, banks: &mut [u32], from: usize, blocks: u32) {
+        let len = banks.len();
+        if len == 0 {
+            return;
+        }
 
-    let input_len = parsed.len();
-    let mut current_memory = parsed;
-    let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
+        let share = blocks / len as u32;
+        let remainder = (blocks % len as u32) as usize;
+
+        for bank in banks.iter_mut() {
+            *bank += share;
+        }
+
+        match self.remainder {
+            Remainder::Following => {
+                for distance in 1..=remainder {
+                    banks[self.neighbour(len, from, distance)] += 1;
+                }
+            }
+            Remainder::First => banks[self.neighbour(len, from, 1)] += remainder as u32,
+        }
+    }
+}
+
+/// How repeated states are recognised.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Detection {
+    /// Remembers a copy of every state.
+    Full,
+    /// Brent's algorithm, keeps only two states around.
+    Brent,
+    /// Remembers a 64 bit fingerprint of every state and replays from the
+    /// start to rule out collisions.
+    Fingerprint,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    /// Redistributions until a state is seen for the second time.
+    pub cycles: u32,
+    pub loop_size: u32,
+    /// Redistributions before the first state that is part of the loop.
+    pub loop_start: u32,
+}
+
+fn fingerprint(banks: &[u32]) -> u64 {
+    banks.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &bank| {
+        let hash = (hash ^ u64::from(bank)).wrapping_mul(0x0000_0100_0000_01b3);
+        hash ^ (hash >> 29)
+    })
+}
+
+fn advance(banks: &[u32], policy: &dyn Redistribution, cycles: u32) -> Vec<u32> {
+    let mut banks = banks.to_vec();
+    for _ in 0..cycles {
+        policy.redistribute(&mut banks);
+    }
+
+    banks
+}
+
+fn find_full(banks: &[u32], policy: &dyn Redistribution) -> Cycle {
+    let mut current_memory = banks.to_vec();
+    let mut seen: Map<Vec<u32>, u32> = Map::new();
     seen.insert(current_memory.clone(), 0);
     let mut cycles = 0;
-    let mut loop_size = 0;
 
     loop {
-        let next = identify_next(&current_memory);
+        policy.redistribute(&mut current_memory);
+        cycles += 1;
+
+        if let Some(&v) = seen.get(&current_memory) {
+            return Cycle {
+                cycles,
+                loop_size: cycles - v,
+                loop_start: v,
+            };
+        }
+        seen.insert(current_memory.clone(), cycles);
+    }
+}
 
-        current_memory[next.0] = 0;
-        let mut i = next.0 + 1;
-        let mut left = next.1;
+fn find_brent(banks: &[u32], policy: &dyn Redistribution) -> Cycle {
+    let mut power = 1;
+    let mut loop_size = 1;
+    let mut tortoise = banks.to_vec();
+    let mut hare = advance(banks, policy, 1);
 
-        while left > 0 {
-            current_memory[i % input_len] += 1;
-            left -= 1;
-            i += 1;
+    while tortoise != hare {
+        if power == loop_size {
+            tortoise.copy_from_slice(&hare);
+            power *= 2;
+            loop_size = 0;
         }
+        policy.redistribute(&mut hare);
+        loop_size += 1;
+    }
+
+    let mut tortoise = banks.to_vec();
+    let mut hare = advance(banks, policy, loop_size);
+    let mut loop_start = 0;
+    while tortoise != hare {
+        policy.redistribute(&mut tortoise);
+        policy.redistribute(&mut hare);
+        loop_start += 1;
+    }
+
+    Cycle {
+        cycles: loop_start + loop_size,
+        loop_size,
+        loop_start,
+    }
+}
+
+fn find_fingerprint(banks: &[u32], policy: &dyn Redistribution) -> Cycle {
+    find_by_hash(banks, policy, &fingerprint)
+}
+
+/// Remembers the first cycle count per hash. States whose hash collides with
+/// an earlier one but that differ from it are kept in a separate list, so a
+/// collision cannot hide the state that really repeats.
+fn find_by_hash(banks: &[u32], policy: &dyn Redistribution, hash: &dyn Fn(&[u32]) -> u64) -> Cycle {
+    let mut current_memory = banks.to_vec();
+    let mut seen: Map<u64, u32> = Map::new();
+    let mut collisions: Vec<(u64, u32)> = Vec::new();
+    seen.insert(hash(&current_memory), 0);
+    let mut cycles = 0;
+
+    loop {
+        policy.redistribute(&mut current_memory);
         cycles += 1;
 
-        if let Some(v) = seen.get(&current_memory) {
-            loop_size = cycles - v;
-            break;
+        let key = hash(&current_memory);
+        let first = match seen.get(&key) {
+            Some(&first) => first,
+            None => {
+                seen.insert(key, cycles);
+                continue;
+            }
+        };
+
+        let earlier = collisions
+            .iter()
+            .filter(|&&(other, _)| other == key)
+            .map(|&(_, v)| v);
+        if let Some(v) = Some(first)
+            .into_iter()
+            .chain(earlier)
+            .find(|&v| advance(banks, policy, v) == current_memory)
+        {
+            return Cycle {
+                cycles,
+                loop_size: cycles - v,
+                loop_start: v,
+            };
         }
-        seen.insert(current_memory.clone(), cycles);
+        collisions.push((key, cycles));
     }
+}
 
-    (cycles, loop_size)
+/// Fails if the banks hold more blocks than fit in a `u32`, which is what
+/// keeps every bank from overflowing while the blocks move around.
+pub fn find_cycle(
+    banks: &[u32],
+    policy: &dyn Redistribution,
+    detection: Detection,
+) -> Result<Cycle, ArithmeticError> {
+    banks
+        .iter()
+        .try_fold(0u32, |total, &bank| total.try_add(bank))?;
+
+    Ok(match detection {
+        Detection::Full => find_full(banks, policy),
+        Detection::Brent => find_brent(banks, policy),
+        Detection::Fingerprint => find_fingerprint(banks, policy),
+    })
+}
+
+pub fn solve_with(input: &str, policy: &dyn Redistribution) -> Result<(u32, u32), String> {
+    let banks = parse(input)?;
+    let cycle = find_cycle(&banks, policy, Detection::Full)
+        .map_err(|error| format!("Too many blocks in the memory banks: {}", error))?;
+
+    Ok((cycle.cycles, cycle.loop_size))
+}
+
+pub fn solve(input: &str) -> Result<(u32, u32), String> {
+    solve_with(input, &Policy::default())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
 
     #[test]
     fn test_cases_star_one() {
-        assert_eq!(solve("0 2 7 0"), (5, 4));
+        assert_eq!(solve("0 2 7 0"), Ok((5, 4)));
     }
 
     #[test]
     fn test_cases_star_two() {
-        assert_eq!(solve("0 2 7 0"), (5, 4));
+        assert_eq!(solve("0 2 7 0"), Ok((5, 4)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("0 2 -7 0").is_err());
+    }
+
+    fn redistributed(policy: Policy, banks: &[u32]) -> Vec<u32> {
+        let mut banks = banks.to_vec();
+        policy.redistribute(&mut banks);
+
+        banks
+    }
+
+    #[test]
+    fn test_policies() {
+        let original = Policy::default();
+        assert_eq!(redistributed(original, &[0, 2, 7, 0]), vec![2, 4, 1, 2]);
+        assert_eq!(redistributed(original, &[3, 1, 3, 0]), vec![0, 2, 4, 1]);
+        assert_eq!(redistributed(original, &[0, 0, 0]), vec![0, 0, 0]);
+        assert_eq!(redistributed(original, &[]), vec![]);
+
+        let highest = Policy {
+            tie: Tie::HighestIndex,
+            ..original
+        };
+        assert_eq!(redistributed(highest, &[3, 1, 3, 0]), vec![4, 2, 0, 1]);
+
+        let left = Policy {
+            direction: Direction::Left,
+            ..original
+        };
+        assert_eq!(
+            redistributed(left, &[4, 0, 0, 0, 0, 0]),
+            vec![0, 0, 1, 1, 1, 1]
+        );
+        assert_eq!(
+            redistributed(original, &[4, 0, 0, 0, 0, 0]),
+            vec![0, 1, 1, 1, 1, 0]
+        );
+
+        let first = Policy {
+            remainder: Remainder::First,
+            ..original
+        };
+        assert_eq!(redistributed(first, &[0, 2, 7, 0]), vec![1, 3, 1, 4]);
+    }
+
+    #[test]
+    fn test_matches_one_block_at_a_time() {
+        let mut banks = vec![0u32, 14, 13, 12, 11, 10, 8, 8, 6, 6, 5, 3, 3, 2, 1, 10];
+        for _ in 0..1000 {
+            let mut expected = banks.clone();
+            let from = Policy::default().select(&expected);
+            let mut left = expected[from];
+            expected[from] = 0;
+            let mut i = from + 1;
+            while left > 0 {
+                expected[i % banks.len()] += 1;
+                left -= 1;
+                i += 1;
+            }
+
+            Policy::default().redistribute(&mut banks);
+            assert_eq!(banks, expected);
+        }
+    }
+
+    #[test]
+    fn test_solve_with_policy() {
+        // Mirroring the banks and the policy gives the same cycles.
+        let mirrored = Policy {
+            tie: Tie::HighestIndex,
+            direction: Direction::Left,
+            remainder: Remainder::Following,
+        };
+
+        assert_eq!(solve_with("0 2 7 0", &Policy::default()), Ok((5, 4)));
+        assert_eq!(solve_with("0 7 2 0", &mirrored), Ok((5, 4)));
+        assert_eq!(
+            solve_with("", &Policy::default()),
+            Err("Expected at least one memory bank".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_detection_modes() {
+        let policies = [
+            Policy::default(),
+            Policy {
+                tie: Tie::HighestIndex,
+                direction: Direction::Left,
+                remainder: Remainder::First,
+            },
+        ];
+
+        for banks in &[
+            vec![0, 2, 7, 0],
+            vec![5, 1, 10, 0, 1, 7, 13, 14, 3, 12, 8, 10, 7, 12, 0, 6],
+        ] {
+            for policy in &policies {
+                let full = find_cycle(banks, policy, Detection::Full).unwrap();
+
+                assert_eq!(find_cycle(banks, policy, Detection::Brent), Ok(full));
+                assert_eq!(find_cycle(banks, policy, Detection::Fingerprint), Ok(full));
+                assert_eq!(full.cycles, full.loop_start + full.loop_size);
+            }
+        }
+
+        // Every state collides, so only replaying tells them apart.
+        for policy in &policies {
+            assert_eq!(
+                Ok(find_by_hash(&[0, 2, 7, 0], policy, &|_| 0)),
+                find_cycle(&[0, 2, 7, 0], policy, Detection::Full)
+            );
+        }
+
+        for detection in &[Detection::Full, Detection::Brent, Detection::Fingerprint] {
+            assert_eq!(
+                find_cycle(&[], &Policy::default(), *detection),
+                Ok(Cycle {
+                    cycles: 1,
+                    loop_size: 1,
+                    loop_start: 0,
+                })
+            );
+            assert_eq!(
+                find_cycle(&[u32::MAX, u32::MAX], &Policy::default(), *detection),
+                Err(ArithmeticError::Overflow)
+            );
+        }
+        assert_eq!(
+            solve("4294967295 4294967295"),
+            Err("Too many blocks in the memory banks: Arithmetic overflow".to_owned())
+        );
+        assert_eq!(solve("4294967295 0"), Ok((33, 2)));
+
+        assert_eq!(
+            find_cycle(&[0, 2, 7, 0], &Policy::default(), Detection::Brent),
+            Ok(Cycle {
+                cycles: 5,
+                loop_size: 4,
+                loop_start: 1,
+            })
+        );
     }
 }
@@ -1,155 +1,792 @@
-use std::collections::{HashMap, HashSet};
-use std::fmt;
+use collections::{Map, Set};
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
 
-struct Program {
+/// Index of a program in its tower's arena.
+pub type ProgramId = usize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
     name: String,
     weight: u32,
-    children: Vec<Box<Program>>,
+    parent: Option<ProgramId>,
+    children: Vec<ProgramId>,
 }
 
 impl Program {
-    fn new(name: String, weight: u32, children: Vec<Box<Program>>) -> Self {
-        Self {
-            name: name,
-            weight: weight,
-            children: children,
-        }
-    }
-
-    fn build(
-        data: &(&str, u32, HashSet<&str>),
-        tree_data: &HashMap<String, (&str, u32, HashSet<&str>)>,
-    ) -> Box<Program> {
-        Box::new(Program::new(
-            data.0.to_owned(),
-            data.1,
-            data.2
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn parent(&self) -> Option<ProgramId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[ProgramId] {
+        &self.children
+    }
+}
+
+/// Several towers sharing an arena, what the input describes when more than
+/// one program has no parent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Forest {
+    pub programs: Vec<Program>,
+    pub roots: Vec<ProgramId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TowerError {
+    /// `line` is 1-based.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    Empty,
+    DuplicateName(String),
+    UnknownChild {
+        parent: String,
+        child: String,
+    },
+    MultipleParents {
+        child: String,
+        parents: (String, String),
+    },
+    MultipleRoots(Forest),
+    /// The names of the programs that hold each other up, in parent order.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for TowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TowerError::Syntax { line, ref message } => write!(f, "Line {}: {}", line, message),
+            TowerError::Empty => write!(f, "Expected at least one program"),
+            TowerError::DuplicateName(ref name) => write!(f, "Program {} is defined twice", name),
+            TowerError::UnknownChild {
+                ref parent,
+                ref child,
+            } => write!(f, "Unknown child {} of {}", child, parent),
+            TowerError::MultipleParents {
+                ref child,
+                ref parents,
+            } => write!(
+                f,
+                "Program {} is held by both {} and {}",
+                child, parents.0, parents.1
+            ),
+            TowerError::MultipleRoots(ref forest) => write!(
+                f,
+                "Expected exactly one root, found {}",
+                forest
+                    .roots
+                    .iter()
+                    .map(|&root| forest.programs[root].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TowerError::Cycle(ref names) => write!(f, "Cycle detected: {}", names.join(" -> ")),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Tower {
+    programs: Vec<Program>,
+    root: ProgramId,
+}
+
+impl Tower {
+    pub fn root(&self) -> &Program {
+        &self.programs[self.root]
+    }
+
+    pub fn program(&self, id: ProgramId) -> &Program {
+        &self.programs[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    /// Always false, a tower holds at least its root.
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// Every program below `id` and `id` itself, children left to right
+    /// before their parent.
+    fn post_order(&self, id: ProgramId) -> Vec<ProgramId> {
+        let mut order = Vec::new();
+        let mut stack = vec![(id, false)];
+
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                order.push(id);
+                continue;
+            }
+
+            stack.push((id, true));
+            stack.extend(
+                self.programs[id]
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, false)),
+            );
+        }
+
+        order
+    }
+
+    /// The total weight of every program's subtower, indexed by id.
+    fn totals(&self) -> Result<Vec<u32>, ArithmeticError> {
+        let mut totals = vec![0; self.programs.len()];
+
+        for id in self.post_order(self.root) {
+            let program = &self.programs[id];
+            totals[id] = program
+                .children
+                .iter()
+                .try_fold(program.weight, |acc, &child| acc.try_add(totals[child]))?;
+        }
+
+        Ok(totals)
+    }
+
+    /// The total weight of every program's subtower, indexed by id, summed
+    /// as `u64` so that any tower can be exported. Fewer than `2^32`
+    /// programs of at most `u32::MAX` each cannot overflow it.
+    fn wide_totals(&self) -> Vec<u64> {
+        let mut totals = vec![0; self.programs.len()];
+
+        for id in self.post_order(self.root) {
+            let program = &self.programs[id];
+            totals[id] = program
+                .children
+                .iter()
+                .fold(u64::from(program.weight), |acc, &child| acc + totals[child]);
+        }
+
+        totals
+    }
+}
+
+impl fmt::Debug for Tower {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        export(self, Format::Text, f)
+    }
+}
+
+/// Lookups by name on a tower, with subtower weights and depths computed
+/// once up front.
+pub struct TowerIndex<'a> {
+    tower: &'a Tower,
+    ids: Map<&'a str, ProgramId>,
+    totals: Vec<u32>,
+    depths: Vec<usize>,
+}
+
+impl<'a> TowerIndex<'a> {
+    pub fn new(tower: &'a Tower) -> Result<Self, ArithmeticError> {
+        let totals = tower.totals()?;
+        let mut depths = vec![0; tower.programs.len()];
+        // Reversed post-order visits every parent before its children.
+        for id in tower.post_order(tower.root).into_iter().rev() {
+            if let Some(parent) = tower.programs[id].parent {
+                depths[id] = depths[parent] + 1;
+            }
+        }
+        let ids = tower
+            .programs
+            .iter()
+            .enumerate()
+            .map(|(id, program)| (program.name.as_str(), id))
+            .collect();
+
+        Ok(TowerIndex {
+            tower,
+            ids,
+            totals,
+            depths,
+        })
+    }
+
+    pub fn id(&self, name: &str) -> Option<ProgramId> {
+        self.ids.get(name).cloned()
+    }
+
+    /// The weight of the program and everything it holds.
+    pub fn total_weight(&self, name: &str) -> Option<u32> {
+        self.id(name).map(|id| self.totals[id])
+    }
+
+    /// The root is at depth 0.
+    pub fn depth(&self, name: &str) -> Option<usize> {
+        self.id(name).map(|id| self.depths[id])
+    }
+
+    /// The names from the root down to and including `name`.
+    pub fn path(&self, name: &str) -> Option<Vec<&'a str>> {
+        let mut id = self.id(name)?;
+        let mut path = vec![self.tower.programs[id].name.as_str()];
+        while let Some(parent) = self.tower.programs[id].parent {
+            path.push(&self.tower.programs[parent].name);
+            id = parent;
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// The other programs held by the same parent, empty for the root.
+    pub fn siblings(&self, name: &str) -> Option<Vec<&'a str>> {
+        let id = self.id(name)?;
+        let programs = &self.tower.programs;
+
+        Some(match programs[id].parent {
+            Some(parent) => programs[parent]
+                .children
                 .iter()
-                .map(|name| Program::build(tree_data.get(name.to_owned()).unwrap(), tree_data))
+                .filter(|&&child| child != id)
+                .map(|&child| programs[child].name.as_str())
                 .collect(),
-        ))
+            None => Vec::new(),
+        })
     }
 
-    fn weight(&self, include_self: bool) -> u32 {
-        let mut sum = 0;
-        if include_self {
-            sum += self.weight;
+    /// The deepest program holding both, which is one of them if it holds
+    /// the other.
+    pub fn lowest_common_ancestor(&self, a: &str, b: &str) -> Option<&'a str> {
+        let (mut a, mut b) = (self.id(a)?, self.id(b)?);
+        let parent = |id: ProgramId| self.tower.programs[id].parent.expect("Root is shallowest");
+
+        while self.depths[a] > self.depths[b] {
+            a = parent(a);
+        }
+        while self.depths[b] > self.depths[a] {
+            b = parent(b);
+        }
+        while a != b {
+            a = parent(a);
+            b = parent(b);
         }
 
-        sum + self
-            .children
+        Some(&self.tower.programs[a].name)
+    }
+}
+
+/// A program whose children do not all carry the same weight.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Imbalance {
+    pub program: String,
+    /// Every child with the total weight of its subtower.
+    pub children: Vec<(String, u32)>,
+    /// The subtower weight a strict majority of the children agree on.
+    pub expected: Option<u32>,
+    /// Every change to the own weight of a single child that balances the
+    /// program. Children that are unbalanced themselves are fixed further
+    /// down and never show up here.
+    pub corrections: Vec<Correction>,
+    /// Children that could balance the program on their own only with a
+    /// weight below zero or above `u32::MAX`.
+    pub impossible: Vec<String>,
+}
+
+/// Changing the own weight of `program` from `weight` to `corrected`
+/// balances its parent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Correction {
+    pub program: String,
+    pub weight: u32,
+    pub corrected: u32,
+    /// Whether every program further up is balanced after the change too.
+    pub balances_tower: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub imbalances: Vec<Imbalance>,
+    /// Programs whose children disagree without a majority, so there is no
+    /// telling which of them is wrong.
+    pub ambiguous: Vec<String>,
+}
+
+impl Diagnosis {
+    /// The candidate corrections of every imbalance, bottom up.
+    pub fn corrections(&self) -> Vec<&Correction> {
+        self.imbalances
             .iter()
-            .fold(0, |acc, child| acc + child.weight(include_self))
+            .flat_map(|imbalance| &imbalance.corrections)
+            .collect()
     }
+}
 
-    fn weight_required(&self, target_weight: u32) -> u32 {
-        let child_weights = self
-            .children
+/// Whether every ancestor of `id` is balanced once its subtower weight
+/// changes by `delta`.
+fn balances_ancestors(tower: &Tower, totals: &[u32], mut id: ProgramId, delta: i64) -> bool {
+    while let Some(parent) = tower.programs[id].parent {
+        let total =
+            |child: ProgramId| i64::from(totals[child]) + if child == id { delta } else { 0 };
+        let children = &tower.programs[parent].children;
+        if !children
             .iter()
-            .map(|child| child.weight(true))
-            .collect::<Vec<u32>>();
-        let balanced = child_weights.iter().all(|x| *x == child_weights[0]);
+            .all(|&child| total(child) == total(children[0]))
+        {
+            return false;
+        }
+        id = parent;
+    }
 
-        if balanced {
-            let child_sum: u32 = child_weights.iter().sum();
-            return (target_weight as i32 - child_sum as i32) as u32;
+    true
+}
+
+/// Finds every imbalance in the tower, bottom up. Fails only if a subtower
+/// weight does not fit into a `u32`.
+pub fn diagnose(tower: &Tower) -> Result<Diagnosis, ArithmeticError> {
+    let totals = tower.totals()?;
+    let mut balanced = vec![true; tower.programs.len()];
+    let mut diagnosis = Diagnosis::default();
+
+    for id in tower.post_order(tower.root) {
+        let program = &tower.programs[id];
+        let children = &program.children;
+        if children
+            .iter()
+            .all(|&child| totals[child] == totals[children[0]])
+        {
+            continue;
         }
 
-        let mut counts = HashMap::<u32, u32>::new();
+        balanced[id] = false;
+        let expected = children.iter().map(|&child| totals[child]).find(|&weight| {
+            children
+                .iter()
+                .filter(|&&child| totals[child] == weight)
+                .count()
+                * 2
+                > children.len()
+        });
+        if expected.is_none() {
+            diagnosis.ambiguous.push(program.name.clone());
+        }
+
+        let mut corrections = Vec::new();
+        let mut impossible = Vec::new();
+        for &child in children.iter().filter(|&&child| balanced[child]) {
+            // Only a child whose siblings all agree can balance on its own.
+            let mut siblings = children.iter().filter(|&&other| other != child);
+            let target = totals[*siblings.next().expect("Imbalance has two children")];
+            if !siblings.all(|&other| totals[other] == target) {
+                continue;
+            }
 
-        for weight in child_weights.iter() {
-            *counts.entry(*weight).or_insert(0) += 1;
+            let weight = tower.programs[child].weight;
+            let delta = i64::from(target) - i64::from(totals[child]);
+            match u32::try_from(i64::from(weight) + delta) {
+                Ok(corrected) => corrections.push(Correction {
+                    program: tower.programs[child].name.clone(),
+                    weight,
+                    corrected,
+                    balances_tower: balances_ancestors(tower, &totals, child, delta),
+                }),
+                Err(_) => impossible.push(tower.programs[child].name.clone()),
+            }
         }
 
-        let mut count_vec: Vec<_> = counts.iter().collect();
-        count_vec.sort_by(|a, b| b.1.cmp(a.1));
-        let index_of_unbalanced_child = child_weights
-            .clone()
-            .iter()
-            .position(|&v| v == *count_vec.iter().last().unwrap().0)
-            .unwrap();
+        diagnosis.imbalances.push(Imbalance {
+            program: program.name.clone(),
+            children: children
+                .iter()
+                .map(|&child| (tower.programs[child].name.clone(), totals[child]))
+                .collect(),
+            expected,
+            corrections,
+            impossible,
+        });
+    }
 
-        return self.children[index_of_unbalanced_child].weight_required(*count_vec[0].0);
+    Ok(diagnosis)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One program per line, indented by depth, with own and total weight.
+    Text,
+    /// A Graphviz digraph with the unbalanced programs in red and the
+    /// programs to correct filled.
+    Dot,
+    /// Nested objects with own and total weight.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name {
+            "text" => Ok(Format::Text),
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "Unknown format {:?}, expected text, dot or json",
+                name
+            )),
+        }
     }
+}
 
-    fn write_tree(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
-        write!(
-            f,
-            "{}{} ({} - {})\n",
+/// Writes `value` as a JSON string.
+fn write_quoted<W: fmt::Write>(out: &mut W, value: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Writes `value` as a DOT quoted string, which only knows escaped quotes,
+/// backslashes and newlines.
+fn write_dot_quoted<W: fmt::Write>(out: &mut W, value: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_text<W: fmt::Write>(tower: &Tower, totals: &[u64], out: &mut W) -> fmt::Result {
+    let mut stack = vec![(tower.root, 0)];
+
+    while let Some((id, depth)) = stack.pop() {
+        let program = &tower.programs[id];
+        writeln!(
+            out,
+            "{}{} ({} - {})",
             "  ".repeat(depth),
-            self.name,
-            self.weight,
-            self.weight(true)
+            program.name,
+            program.weight,
+            totals[id]
+        )?;
+        stack.extend(
+            program
+                .children
+                .iter()
+                .rev()
+                .map(|&child| (child, depth + 1)),
         );
+    }
 
-        self.children.iter().for_each(|child| {
-            child.write_tree(f, depth + 1);
-        });
+    Ok(())
+}
+
+fn write_dot<W: fmt::Write>(tower: &Tower, totals: &[u64], out: &mut W) -> fmt::Result {
+    // A tower too heavy to diagnose is still drawn, just without highlights.
+    let diagnosis = diagnose(tower).ok();
+    let unbalanced = diagnosis
+        .iter()
+        .flat_map(|diagnosis| &diagnosis.imbalances)
+        .map(|imbalance| imbalance.program.as_str())
+        .collect::<Set<_>>();
+    let corrections = diagnosis
+        .iter()
+        .flat_map(|diagnosis| diagnosis.corrections())
+        .map(|correction| (correction.program.as_str(), correction.corrected))
+        .collect::<Map<_, _>>();
 
-        Ok(())
+    writeln!(out, "digraph tower {{")?;
+    if diagnosis.is_none() {
+        writeln!(
+            out,
+            "    // Subtower weights overflow u32, imbalances are not highlighted."
+        )?;
     }
+    for (id, program) in tower.programs.iter().enumerate() {
+        write!(out, "    n{} [label=", id)?;
+        let label = match corrections.get(program.name.as_str()) {
+            Some(corrected) => format!(
+                "{}\n{} -> {} - {}",
+                program.name, program.weight, corrected, totals[id]
+            ),
+            None => format!("{}\n{} - {}", program.name, program.weight, totals[id]),
+        };
+        write_dot_quoted(out, &label)?;
+        if unbalanced.contains(program.name.as_str()) {
+            write!(out, ", color=red")?;
+        }
+        if corrections.contains_key(program.name.as_str()) {
+            write!(out, ", style=filled, fillcolor=orange")?;
+        }
+        writeln!(out, "];")?;
+    }
+    for (id, program) in tower.programs.iter().enumerate() {
+        for child in &program.children {
+            writeln!(out, "    n{} -> n{};", id, child)?;
+        }
+    }
+    writeln!(out, "}}")
 }
 
-impl fmt::Debug for Program {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.write_tree(f, 0)
+fn write_json<W: fmt::Write>(tower: &Tower, totals: &[u64], out: &mut W) -> fmt::Result {
+    // `None` closes the children of the program opened before it.
+    let mut stack = vec![Some(tower.root)];
+    let mut first = true;
+
+    while let Some(entry) = stack.pop() {
+        let id = match entry {
+            Some(id) => id,
+            None => {
+                out.write_str("]}")?;
+                first = false;
+                continue;
+            }
+        };
+
+        if !first {
+            out.write_char(',')?;
+        }
+        let program = &tower.programs[id];
+        out.write_str("{\"name\":")?;
+        write_quoted(out, &program.name)?;
+        write!(
+            out,
+            ",\"weight\":{},\"total\":{},\"children\":[",
+            program.weight, totals[id]
+        )?;
+        first = true;
+        stack.push(None);
+        stack.extend(program.children.iter().rev().map(|&child| Some(child)));
     }
+
+    writeln!(out)
 }
 
-fn parse<'a>(input: &'a str) -> Box<Program> {
-    let tree_map = input
-        .trim()
-        .lines()
-        .map(|row| {
-            let words = row.trim().split_whitespace().collect::<Vec<&str>>();
-            let name = words[0];
-            let weight = words[1]
-                .trim_matches(|c: char| !c.is_numeric())
-                .parse::<u32>()
-                .expect("Expected numeric weight");
-            let mut children = HashSet::new();
+/// Writes the tower in the given format. Fails only if `out` does.
+pub fn export<W: fmt::Write>(tower: &Tower, format: Format, out: &mut W) -> fmt::Result {
+    let totals = tower.wide_totals();
 
-            if words.len() > 3 {
-                children = words[3..]
-                    .iter()
-                    .map(|program| program.trim_matches(','))
-                    .collect::<HashSet<&str>>();
-            }
+    match format {
+        Format::Text => write_text(tower, &totals, out),
+        Format::Dot => write_dot(tower, &totals, out),
+        Format::Json => write_json(tower, &totals, out),
+    }
+}
+
+fn parse_row(row: &str) -> Result<(&str, u32, Vec<&str>), String> {
+    let words = row.split_whitespace().collect::<Vec<&str>>();
+    if words.len() < 2 || words.len() == 3 || (words.len() > 3 && words[2] != "->") {
+        return Err(format!(
+            "Expected `<name> (<weight>) [-> <children>]` found {:?}",
+            row
+        ));
+    }
+
+    let name = words[0];
+    let weight = words[1]
+        .trim_matches(|c: char| !c.is_numeric())
+        .parse::<u32>()
+        .map_err(|_| format!("Expected numeric weight in {:?}", row))?;
+    let mut children = Vec::new();
+
+    if words.len() > 3 {
+        children = words[3..]
+            .iter()
+            .map(|program| program.trim_matches(','))
+            .collect::<Vec<&str>>();
+    }
+
+    Ok((name, weight, children))
+}
+
+/// A cycle among the programs that are not reachable from any root, found
+/// by following parents until a program repeats.
+fn find_cycle(programs: &[Program], reachable: &[bool]) -> Option<Vec<String>> {
+    let start = reachable.iter().position(|&reached| !reached)?;
+    let mut visited = vec![false; programs.len()];
+    let mut id = start;
+
+    while !visited[id] {
+        visited[id] = true;
+        id = programs[id].parent?;
+    }
 
-            (name.to_owned(), (name, weight, children))
-        }).collect::<HashMap<_, _>>();
-    let mut root = tree_map.keys().collect::<HashSet<_>>();
-    let childs_of_other = tree_map
-        .values()
-        .flat_map(|&(_, _, ref children)| children.clone())
-        .collect::<HashSet<&str>>();
+    let mut cycle = vec![programs[id].name.clone()];
+    let mut next = programs[id].parent?;
+    while next != id {
+        cycle.push(programs[next].name.clone());
+        next = programs[next].parent?;
+    }
+    cycle.reverse();
+    cycle.rotate_right(1);
+
+    Some(cycle)
+}
 
-    for node in tree_map.values() {
-        if childs_of_other.contains(node.0) {
-            root.remove(&node.0.to_owned());
+/// Builds the tower without recursion, so any depth works.
+pub fn parse(input: &str) -> Result<Tower, TowerError> {
+    let mut rows = Vec::new();
+    let mut ids: Map<&str, ProgramId> = Map::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
         }
+
+        let row = parse_row(line).map_err(|message| TowerError::Syntax {
+            line: i + 1,
+            message,
+        })?;
+        if ids.insert(row.0, rows.len()).is_some() {
+            return Err(TowerError::DuplicateName(row.0.to_owned()));
+        }
+        rows.push(row);
+    }
+
+    let mut programs = rows
+        .iter()
+        .map(|&(name, weight, _)| Program {
+            name: name.to_owned(),
+            weight,
+            parent: None,
+            children: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    for (id, &(name, _, ref children)) in rows.iter().enumerate() {
+        for child_name in children {
+            let child = *ids
+                .get(child_name)
+                .ok_or_else(|| TowerError::UnknownChild {
+                    parent: name.to_owned(),
+                    child: child_name.to_string(),
+                })?;
+
+            if let Some(parent) = programs[child].parent {
+                return Err(TowerError::MultipleParents {
+                    child: child_name.to_string(),
+                    parents: (programs[parent].name.clone(), name.to_owned()),
+                });
+            }
+            programs[child].parent = Some(id);
+            programs[id].children.push(child);
+        }
+    }
+
+    if programs.is_empty() {
+        return Err(TowerError::Empty);
+    }
+
+    let roots = (0..programs.len())
+        .filter(|&id| programs[id].parent.is_none())
+        .collect::<Vec<_>>();
+    let mut reachable = vec![false; programs.len()];
+    let mut stack = roots.clone();
+    while let Some(id) = stack.pop() {
+        reachable[id] = true;
+        stack.extend(&programs[id].children);
+    }
+
+    if let Some(cycle) = find_cycle(&programs, &reachable) {
+        return Err(TowerError::Cycle(cycle));
+    }
+
+    if roots.len() != 1 {
+        return Err(TowerError::MultipleRoots(Forest { programs, roots }));
     }
 
-    assert!(root.len() == 1, "There can only be one");
+    Ok(Tower {
+        programs,
+        root: roots[0],
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    Tower(TowerError),
+    Arithmetic(ArithmeticError),
+    /// The tower is balanced, or no single weight change balances all of it.
+    NoSingleCorrection(Diagnosis),
+}
+
+impl From<TowerError> for SolveError {
+    fn from(error: TowerError) -> Self {
+        SolveError::Tower(error)
+    }
+}
+
+impl From<ArithmeticError> for SolveError {
+    fn from(error: ArithmeticError) -> Self {
+        SolveError::Arithmetic(error)
+    }
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SolveError::Tower(ref error) => write!(f, "{}", error),
+            SolveError::Arithmetic(ref error) => write!(f, "{}", error),
+            SolveError::NoSingleCorrection(ref diagnosis) => write!(
+                f,
+                "Expected a single correction balancing the tower, found {} imbalances",
+                diagnosis.imbalances.len()
+            ),
+        }
+    }
+}
 
-    let root_vec = root.iter().collect::<Vec<_>>();
-    let root_data = tree_map.get(root_vec[0].to_owned()).unwrap();
+pub fn solve(input: &str) -> Result<(String, u32), SolveError> {
+    let tower = parse(input)?;
+    let diagnosis = diagnose(&tower)?;
+    let corrected = {
+        let mut corrections = diagnosis
+            .corrections()
+            .into_iter()
+            .filter(|correction| correction.balances_tower);
+        match (corrections.next(), corrections.next()) {
+            (Some(correction), None) => Some(correction.corrected),
+            _ => None,
+        }
+    };
 
-    Program::build(root_data, &tree_map)
+    match corrected {
+        Some(corrected) => Ok((tower.root().name.to_owned(), corrected)),
+        None => Err(SolveError::NoSingleCorrection(diagnosis)),
+    }
 }
 
-pub fn solve(input: &str) -> (String, u32) {
-    let tree = parse(input);
-    (tree.name.to_owned(), tree.weight_required(0))
+pub fn visualize(input: &str) -> String {
+    match parse(input) {
+        Ok(tower) => format!("{:?}", tower),
+        Err(error) => error.to_string(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
+    use integer::ArithmeticError;
+    #[cfg(feature = "std")]
+    use snapshot::assert_snapshot;
 
-    #[test]
-    fn test_cases() {
-        let input = "
+    const EXAMPLE: &str = "
             pbga (66)
             xhth (57)
             ebii (61)
@@ -164,6 +801,312 @@ mod tests {
             gyxo (61)
             cntj (57)
         ";
-        assert_eq!(solve(input), ("tknk".to_owned(), 60));
+
+    #[test]
+    fn test_cases() {
+        assert_eq!(solve(EXAMPLE), Ok(("tknk".to_owned(), 60)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_tree_snapshot() {
+        let tree = parse(EXAMPLE).unwrap();
+
+        assert_snapshot("day7_example_tree", &format!("{:?}", tree));
+    }
+
+    #[test]
+    fn test_index() {
+        let tower = parse(EXAMPLE).unwrap();
+        let index = TowerIndex::new(&tower).unwrap();
+
+        assert_eq!(index.total_weight("tknk"), Some(778));
+        assert_eq!(index.total_weight("ugml"), Some(251));
+        assert_eq!(index.total_weight("nope"), None);
+        assert_eq!(index.depth("tknk"), Some(0));
+        assert_eq!(index.depth("jptl"), Some(2));
+        assert_eq!(index.path("jptl"), Some(vec!["tknk", "ugml", "jptl"]));
+        assert_eq!(index.path("tknk"), Some(vec!["tknk"]));
+        assert_eq!(index.siblings("padx"), Some(vec!["ugml", "fwft"]));
+        assert_eq!(index.siblings("tknk"), Some(vec![]));
+        assert_eq!(index.lowest_common_ancestor("gyxo", "jptl"), Some("ugml"));
+        assert_eq!(index.lowest_common_ancestor("gyxo", "cntj"), Some("tknk"));
+        assert_eq!(index.lowest_common_ancestor("padx", "qoyq"), Some("padx"));
+        assert_eq!(index.lowest_common_ancestor("ebii", "ebii"), Some("ebii"));
+        assert_eq!(index.lowest_common_ancestor("ebii", "nope"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_export() {
+        let tower = parse(EXAMPLE).unwrap();
+        let mut dot = String::new();
+        let mut json = String::new();
+        export(&tower, Format::Dot, &mut dot).unwrap();
+        export(&tower, Format::Json, &mut json).unwrap();
+
+        assert_snapshot("day7_example_dot", &dot);
+        assert_snapshot("day7_example_json", &json);
+        assert_eq!("dot".parse::<Format>(), Ok(Format::Dot));
+        assert!("yaml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_export_escapes_names() {
+        let tower = parse("a\"b (1) -> c\\d\u{1}\nc\\d\u{1} (2)").unwrap();
+        let mut dot = String::new();
+        let mut json = String::new();
+        export(&tower, Format::Dot, &mut dot).unwrap();
+        export(&tower, Format::Json, &mut json).unwrap();
+
+        assert_eq!(
+            dot.lines().collect::<Vec<_>>(),
+            vec![
+                "digraph tower {",
+                "    n0 [label=\"a\\\"b\\n1 - 3\"];",
+                "    n1 [label=\"c\\\\d\u{1}\\n2 - 2\"];",
+                "    n0 -> n1;",
+                "}",
+            ]
+        );
+        assert_eq!(
+            json,
+            "{\"name\":\"a\\\"b\",\"weight\":1,\"total\":3,\"children\":[\
+             {\"name\":\"c\\\\d\\u0001\",\"weight\":2,\"total\":2,\"children\":[]}]}\n"
+        );
+    }
+
+    #[test]
+    fn test_export_heavy_tower() {
+        let input = "a (4294967295) -> b\nb (1)";
+        let tower = parse(input).unwrap();
+        let mut dot = String::new();
+        export(&tower, Format::Dot, &mut dot).unwrap();
+
+        assert_eq!(
+            format!("{:?}", tower),
+            "a (4294967295 - 4294967296)\n  b (1 - 1)\n"
+        );
+        assert_eq!(visualize(input), format!("{:?}", tower));
+        assert!(dot.contains("label=\"a\\n4294967295 - 4294967296\"];"));
+        assert_eq!(diagnose(&tower), Err(ArithmeticError::Overflow));
+    }
+
+    #[test]
+    fn test_negative_correction() {
+        let input = "
+            root (1) -> a, b, c
+            a (1)
+            b (1)
+            c (1) -> d, e
+            d (10)
+            e (10)
+        ";
+        let diagnosis = diagnose(&parse(input).unwrap()).unwrap();
+
+        assert_eq!(diagnosis.imbalances[0].impossible, vec!["c".to_owned()]);
+        assert!(diagnosis.corrections().is_empty());
+        assert_eq!(solve(input), Err(SolveError::NoSingleCorrection(diagnosis)));
+    }
+
+    #[test]
+    fn test_solve_errors() {
+        assert_eq!(
+            solve("a (1) -> b"),
+            Err(SolveError::Tower(TowerError::UnknownChild {
+                parent: "a".to_owned(),
+                child: "b".to_owned(),
+            }))
+        );
+        assert_eq!(
+            solve("a (4294967295) -> b, c\nb (1)\nc (1)"),
+            Err(SolveError::Arithmetic(ArithmeticError::Overflow))
+        );
+    }
+
+    #[test]
+    fn test_no_single_correction() {
+        let balanced = "a (1) -> b, c\nb (2)\nc (2)";
+        let ambiguous = "a (1) -> b, c\nb (2)\nc (3)";
+
+        match solve(balanced) {
+            Err(SolveError::NoSingleCorrection(diagnosis)) => {
+                assert!(diagnosis.imbalances.is_empty())
+            }
+            other => panic!("Expected no correction, got {:?}", other),
+        }
+        match solve(ambiguous) {
+            Err(SolveError::NoSingleCorrection(diagnosis)) => {
+                assert_eq!(diagnosis.ambiguous, vec!["a".to_owned()]);
+                assert_eq!(
+                    diagnosis
+                        .corrections()
+                        .iter()
+                        .map(|correction| (correction.program.as_str(), correction.corrected))
+                        .collect::<Vec<_>>(),
+                    vec![("b", 3), ("c", 2)]
+                );
+            }
+            other => panic!("Expected two candidates, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("a").is_err());
+        assert!(parse("a (x)").is_err());
+        assert!(parse("a (1) ->").is_err());
+        assert!(parse("a (1) b").is_err());
+        assert_eq!(
+            parse("a (1)\n\nb (x)"),
+            Err(TowerError::Syntax {
+                line: 3,
+                message: "Expected numeric weight in \"b (x)\"".to_owned(),
+            })
+        );
+        assert_eq!(parse(" \n"), Err(TowerError::Empty));
+        assert_eq!(
+            parse("a (1) -> b"),
+            Err(TowerError::UnknownChild {
+                parent: "a".to_owned(),
+                child: "b".to_owned(),
+            })
+        );
+        assert_eq!(
+            parse("a (1)\na (2)"),
+            Err(TowerError::DuplicateName("a".to_owned()))
+        );
+        assert_eq!(
+            parse("a (1) -> c\nb (1) -> c\nc (1)"),
+            Err(TowerError::MultipleParents {
+                child: "c".to_owned(),
+                parents: ("a".to_owned(), "b".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse("a (1) -> b, b\nb (1)"),
+            Err(TowerError::MultipleParents {
+                child: "b".to_owned(),
+                parents: ("a".to_owned(), "a".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse("r (1)\nc (1) -> a\na (1) -> b\nb (1) -> c"),
+            Err(TowerError::Cycle(vec![
+                "c".to_owned(),
+                "a".to_owned(),
+                "b".to_owned(),
+            ]))
+        );
+        assert_eq!(
+            parse("a (1) -> a"),
+            Err(TowerError::Cycle(vec!["a".to_owned()]))
+        );
+
+        match parse("a (1)\nb (2) -> c\nc (3)") {
+            Err(TowerError::MultipleRoots(forest)) => {
+                assert_eq!(forest.roots, vec![0, 1]);
+                assert_eq!(forest.programs[1].children(), &[2]);
+            }
+            other => panic!("Expected a forest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_deep_tower() {
+        let depth = 1_000_000;
+        let mut input = (0..depth - 1)
+            .map(|i| format!("p{} (1) -> p{}\n", i, i + 1))
+            .collect::<String>();
+        input.push_str(&format!("p{} (1)\n", depth - 1));
+
+        let tower = parse(&input).unwrap();
+        assert_eq!(tower.len(), depth);
+        assert_eq!(tower.root().name(), "p0");
+        let index = TowerIndex::new(&tower).unwrap();
+        assert_eq!(index.total_weight("p0"), Some(depth as u32));
+        assert_eq!(index.depth("p999999"), Some(depth - 1));
+        assert_eq!(index.path("p999999").unwrap().len(), depth);
+        assert_eq!(
+            index.lowest_common_ancestor("p999999", "p500000"),
+            Some("p500000")
+        );
+        assert!(diagnose(&tower).unwrap().imbalances.is_empty());
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let diagnosis = diagnose(&parse(EXAMPLE).unwrap()).unwrap();
+
+        assert_eq!(
+            diagnosis.imbalances,
+            vec![Imbalance {
+                program: "tknk".to_owned(),
+                children: vec![
+                    ("ugml".to_owned(), 251),
+                    ("padx".to_owned(), 243),
+                    ("fwft".to_owned(), 243),
+                ],
+                expected: Some(243),
+                corrections: vec![Correction {
+                    program: "ugml".to_owned(),
+                    weight: 68,
+                    corrected: 60,
+                    balances_tower: true,
+                }],
+                impossible: vec![],
+            }]
+        );
+        assert!(diagnosis.ambiguous.is_empty());
+    }
+
+    #[test]
+    fn test_diagnose_several_faults() {
+        let input = "
+            root (1) -> a, b, c, g
+            a (10) -> d, e, f
+            b (20)
+            c (20)
+            d (3)
+            e (3)
+            f (4)
+            x (2) -> y, z
+            y (1)
+            z (2)
+            g (1) -> x, h
+            h (7)
+        ";
+        let diagnosis = diagnose(&parse(input).unwrap()).unwrap();
+
+        assert_eq!(
+            diagnosis
+                .imbalances
+                .iter()
+                .map(|imbalance| imbalance.program.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "x", "g", "root"]
+        );
+        // Each of these balances its parent, but none the whole tower.
+        assert_eq!(
+            diagnosis
+                .corrections()
+                .iter()
+                .map(|correction| (
+                    correction.program.as_str(),
+                    correction.corrected,
+                    correction.balances_tower
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("f", 3, false),
+                ("y", 2, false),
+                ("z", 1, false),
+                ("h", 5, false)
+            ]
+        );
+        assert_eq!(diagnosis.imbalances[3].expected, Some(20));
+        assert!(diagnosis.imbalances[3].corrections.is_empty());
+        assert_eq!(diagnosis.ambiguous, vec!["x".to_owned(), "g".to_owned()]);
+        assert!(solve(input).is_err());
     }
 }
@@ -1,6 +1,15 @@
-use std::collections::HashMap;
+use collections::{Map, Set};
+use core::fmt;
+use integer::{ArithmeticError, Integer};
+use prelude::*;
 
-enum Operator {
+const KEYWORDS: [&str; 4] = ["if", "and", "or", "not"];
+
+/// Deeper conditions are rejected rather than risking the stack.
+const MAX_CONDITION_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
     LessThan,
     LessThanOrEqual,
     GreaterThan,
@@ -22,194 +31,809 @@ impl Operator {
         }
     }
 
-    fn evaluate(&self, operator_value: i32, test_value: i32) -> bool {
+    fn evaluate<T: Integer>(self, left: T, right: T) -> bool {
         match self {
-            &Operator::Equal => operator_value == test_value,
-            &Operator::NotEqual => operator_value != test_value,
-            &Operator::LessThan => test_value < operator_value,
-            &Operator::LessThanOrEqual => test_value <= operator_value,
-            &Operator::GreaterThan => test_value > operator_value,
-            &Operator::GreaterThanOrEqual => test_value >= operator_value,
+            Operator::Equal => left == right,
+            Operator::NotEqual => left != right,
+            Operator::LessThan => left < right,
+            Operator::LessThanOrEqual => left <= right,
+            Operator::GreaterThan => left > right,
+            Operator::GreaterThanOrEqual => left >= right,
         }
     }
 }
 
-struct Condition {
-    target_register: String,
-    operator: Operator,
-    value: i32,
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Operator::LessThan => "<",
+            Operator::LessThanOrEqual => "<=",
+            Operator::GreaterThan => ">",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+        })
+    }
+}
+
+/// A literal or the current value of a register.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand<T> {
+    Value(T),
+    Register(String),
 }
 
-impl Condition {
-    fn new(target_register: String, operator: Operator, value: i32) -> Self {
-        Condition {
-            target_register: target_register,
-            operator: operator,
-            value: value,
+impl<T: Integer> Operand<T> {
+    fn parse(token: &str) -> Result<Operand<T>, String> {
+        let digits = token.trim_start_matches(&['-', '+'][..]);
+        if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            return token
+                .parse::<T>()
+                .map(Operand::Value)
+                .map_err(|_| format!("Invalid number {:?}", token));
         }
+
+        parse_register(token).map(|register| Operand::Register(register.to_owned()))
     }
 
-    fn parse(tokens: &[&str]) -> Option<Condition> {
-        assert!(
-            tokens.len() == 3,
-            "There should be exactly three condition tokens"
-        );
-        if tokens.len() != 3 {
-            return None;
+    fn value(&self, registers: &Registers<T>) -> T {
+        match *self {
+            Operand::Value(value) => value,
+            Operand::Register(ref register) => registers.get(register),
         }
+    }
+
+    pub fn register(&self) -> Option<&str> {
+        match *self {
+            Operand::Value(_) => None,
+            Operand::Register(ref register) => Some(register),
+        }
+    }
 
-        let target_register = tokens[0];
-        let operator = Operator::parse(tokens[1]).expect(&format!("Bad operator {}", tokens[1]));
-        let value = tokens[2]
-            .parse::<i32>()
-            .expect(&format!("Bad condition value {}", tokens[2]));
+    /// The value if it is known without running the program, `written`
+    /// tells whether a register may have been written yet.
+    fn constant(&self, written: &dyn Fn(&str) -> bool) -> Option<T> {
+        match *self {
+            Operand::Value(value) => Some(value),
+            Operand::Register(ref register) if !written(register) => Some(T::zero()),
+            Operand::Register(_) => None,
+        }
+    }
+}
 
-        Some(Condition::new(target_register.to_owned(), operator, value))
+impl<T: Integer> fmt::Display for Operand<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Value(value) => write!(f, "{}", value),
+            Operand::Register(ref register) => f.write_str(register),
+        }
     }
+}
 
-    fn evaluate(&self, test_value: i32) -> bool {
-        self.operator.evaluate(self.value, test_value)
+fn parse_register(token: &str) -> Result<&str, String> {
+    let valid = token.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&token);
+
+    if valid {
+        Ok(token)
+    } else {
+        Err(format!("Invalid register name {:?}", token))
     }
 }
 
-struct Expression<'a> {
-    target_register: &'a str,
-    operation: Operation,
-    condition: Condition,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition<T> {
+    Compare(Operand<T>, Operator, Operand<T>),
+    And(Box<Condition<T>>, Box<Condition<T>>),
+    Or(Box<Condition<T>>, Box<Condition<T>>),
+    Not(Box<Condition<T>>),
+}
+
+impl<T: Integer> Condition<T> {
+    pub fn parse(tokens: &[&str]) -> Result<Condition<T>, String> {
+        let mut parser = ConditionParser {
+            tokens,
+            position: 0,
+            depth: 0,
+        };
+        let condition = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(condition),
+            Some(token) => Err(format!("Unexpected {:?} after condition", token)),
+        }
+    }
+
+    fn evaluate(&self, registers: &Registers<T>) -> bool {
+        match *self {
+            Condition::Compare(ref left, operator, ref right) => {
+                operator.evaluate(left.value(registers), right.value(registers))
+            }
+            Condition::And(ref left, ref right) => {
+                left.evaluate(registers) && right.evaluate(registers)
+            }
+            Condition::Or(ref left, ref right) => {
+                left.evaluate(registers) || right.evaluate(registers)
+            }
+            Condition::Not(ref condition) => !condition.evaluate(registers),
+        }
+    }
+
+    /// Every register the condition reads, in order of appearance.
+    pub fn registers(&self) -> Vec<&str> {
+        match *self {
+            Condition::Compare(ref left, _, ref right) => left
+                .register()
+                .into_iter()
+                .chain(right.register())
+                .collect(),
+            Condition::And(ref left, ref right) | Condition::Or(ref left, ref right) => {
+                let mut registers = left.registers();
+                registers.extend(right.registers());
+                registers
+            }
+            Condition::Not(ref condition) => condition.registers(),
+        }
+    }
+
+    /// The outcome if it does not depend on any register that may have been
+    /// written, see `Operand::constant`.
+    fn constant(&self, written: &dyn Fn(&str) -> bool) -> Option<bool> {
+        match *self {
+            Condition::Compare(ref left, operator, ref right) => {
+                if left.register().is_some() && left == right {
+                    return Some(operator.evaluate(0, 0));
+                }
+
+                Some(operator.evaluate(left.constant(written)?, right.constant(written)?))
+            }
+            Condition::And(ref left, ref right) => {
+                match (left.constant(written), right.constant(written)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            Condition::Or(ref left, ref right) => {
+                match (left.constant(written), right.constant(written)) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+            Condition::Not(ref condition) => condition.constant(written).map(|value| !value),
+        }
+    }
 }
 
-impl<'a> Expression<'a> {
-    fn new(target_register: &'a str, operation: Operation, condition: Condition) -> Expression<'a> {
-        Expression {
-            target_register: target_register,
-            operation: operation,
-            condition: condition,
+impl<T: Integer> fmt::Display for Condition<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::Compare(ref left, operator, ref right) => {
+                write!(f, "{} {} {}", left, operator, right)
+            }
+            Condition::And(ref left, ref right) => write!(f, "({} and {})", left, right),
+            Condition::Or(ref left, ref right) => write!(f, "({} or {})", left, right),
+            Condition::Not(ref condition) => write!(f, "not {}", condition),
         }
     }
 }
 
-enum Operation {
-    Inc(i32),
-    Dec(i32),
+/// `not` binds tighter than `and`, which binds tighter than `or`.
+struct ConditionParser<'a, 't> {
+    tokens: &'t [&'a str],
+    position: usize,
+    depth: usize,
 }
 
-impl Operation {
-    fn parse(tokens: &[&str]) -> Option<Operation> {
-        assert!(
-            tokens.len() == 2,
-            "There should be exactly two operation tokens"
-        );
-        if tokens.len() != 2 {
-            return None;
+impl<'a, 't> ConditionParser<'a, 't> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a str, String> {
+        let token = self
+            .peek()
+            .ok_or_else(|| format!("Expected {}, found end of line", expected))?;
+        self.position += 1;
+
+        Ok(token)
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
         }
+    }
 
-        let value = tokens[1].parse::<i32>();
-        if value.is_err() {
-            return None;
+    fn or<T: Integer>(&mut self) -> Result<Condition<T>, String> {
+        let mut condition = self.and()?;
+        while self.eat("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
         }
 
-        match tokens[0].trim().to_lowercase().as_ref() {
-            "inc" => Some(Operation::Inc(value.unwrap())),
-            "dec" => Some(Operation::Dec(value.unwrap())),
-            _ => None,
+        Ok(condition)
+    }
+
+    fn and<T: Integer>(&mut self) -> Result<Condition<T>, String> {
+        let mut condition = self.unary()?;
+        while self.eat("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn unary<T: Integer>(&mut self) -> Result<Condition<T>, String> {
+        self.depth += 1;
+        if self.depth > MAX_CONDITION_DEPTH {
+            return Err("Condition nested too deeply".to_owned());
+        }
+
+        let condition = self.unary_body();
+        self.depth -= 1;
+
+        condition
+    }
+
+    fn unary_body<T: Integer>(&mut self) -> Result<Condition<T>, String> {
+        if self.eat("not") {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+
+        if self.eat("(") {
+            let condition = self.or()?;
+            return match self.next("`)`")? {
+                ")" => Ok(condition),
+                token => Err(format!("Expected `)`, found {:?}", token)),
+            };
         }
+
+        let left = Operand::parse(self.next("a comparison")?)?;
+        let token = self.next("a comparison operator")?;
+        let operator =
+            Operator::parse(token).ok_or_else(|| format!("Unknown comparison {:?}", token))?;
+        let right = Operand::parse(self.next("an operand")?)?;
+
+        Ok(Condition::Compare(left, operator, right))
     }
 }
 
-impl Operation {
-    fn value(&self) -> i32 {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation<T> {
+    Inc(Operand<T>),
+    Dec(Operand<T>),
+    Set(Operand<T>),
+    Mul(Operand<T>),
+    Div(Operand<T>),
+    Mod(Operand<T>),
+}
+
+impl<T: Integer> Operation<T> {
+    fn parse(operation: &str, operand: &str) -> Result<Operation<T>, String> {
+        let operand = Operand::parse(operand)?;
+
+        match operation.to_lowercase().as_ref() {
+            "inc" => Ok(Operation::Inc(operand)),
+            "dec" => Ok(Operation::Dec(operand)),
+            "set" => Ok(Operation::Set(operand)),
+            "mul" => Ok(Operation::Mul(operand)),
+            "div" => Ok(Operation::Div(operand)),
+            "mod" => Ok(Operation::Mod(operand)),
+            _ => Err(format!("Unknown operation {:?}", operation)),
+        }
+    }
+
+    pub fn operand(&self) -> &Operand<T> {
+        match *self {
+            Operation::Inc(ref operand)
+            | Operation::Dec(ref operand)
+            | Operation::Set(ref operand)
+            | Operation::Mul(ref operand)
+            | Operation::Div(ref operand)
+            | Operation::Mod(ref operand) => operand,
+        }
+    }
+
+    fn apply(&self, value: T, operand: T) -> Result<T, ArithmeticError> {
         match *self {
-            Operation::Inc(v) => v,
-            Operation::Dec(v) => -v,
+            Operation::Inc(_) => value.try_add(operand),
+            Operation::Dec(_) => value.try_sub(operand),
+            Operation::Set(_) => Ok(operand),
+            Operation::Mul(_) => value.try_mul(operand),
+            Operation::Div(_) => value.try_div(operand),
+            Operation::Mod(_) => value.try_rem(operand),
+        }
+    }
+}
+
+impl<T: Integer> fmt::Display for Operation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Operation::Inc(_) => "inc",
+            Operation::Dec(_) => "dec",
+            Operation::Set(_) => "set",
+            Operation::Mul(_) => "mul",
+            Operation::Div(_) => "div",
+            Operation::Mod(_) => "mod",
+        };
+
+        write!(f, "{} {}", name, self.operand())
+    }
+}
+
+/// `<register> <operation> <operand> if <condition>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression<T> {
+    pub target_register: String,
+    pub operation: Operation<T>,
+    pub condition: Condition<T>,
+}
+
+impl<T: Integer> Expression<T> {
+    pub fn parse(row: &str) -> Result<Expression<T>, String> {
+        let tokens = tokenize(row);
+        if tokens.len() < 7 {
+            return Err(format!(
+                "Expected at least 7 tokens per expression. Found {} in {:?}",
+                tokens.len(),
+                row.trim()
+            ));
+        }
+        if tokens[3] != "if" {
+            return Err(format!("Expected `if`, found {:?}", tokens[3]));
+        }
+
+        Ok(Expression {
+            target_register: parse_register(tokens[0])?.to_owned(),
+            operation: Operation::parse(tokens[1], tokens[2])?,
+            condition: Condition::parse(&tokens[4..])?,
+        })
+    }
+}
+
+impl<T: Integer> fmt::Display for Expression<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} if {}",
+            self.target_register, self.operation, self.condition
+        )
+    }
+}
+
+/// Splits on whitespace, with parentheses as tokens of their own.
+fn tokenize(row: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+
+    for word in row.split_whitespace() {
+        let mut start = 0;
+        for (i, c) in word.char_indices() {
+            if c == '(' || c == ')' {
+                if start < i {
+                    tokens.push(&word[start..i]);
+                }
+                tokens.push(&word[i..=i]);
+                start = i + 1;
+            }
+        }
+        if start < word.len() {
+            tokens.push(&word[start..]);
         }
     }
+
+    tokens
 }
 
-struct Registers<'a> {
-    registers: HashMap<&'a str, i32>,
-    largest_observed_value: i32,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based.
+    pub line: usize,
+    pub message: String,
 }
 
-impl<'a> Registers<'a> {
-    fn new() -> Self {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+pub struct Registers<T> {
+    registers: Map<String, T>,
+    largest_observed_value: T,
+}
+
+impl<T: Integer> Default for Registers<T> {
+    fn default() -> Self {
+        Registers::new()
+    }
+}
+
+impl<T: Integer> Registers<T> {
+    pub fn new() -> Self {
         Registers {
-            registers: HashMap::<_, _>::new(),
-            largest_observed_value: 0,
+            registers: Map::new(),
+            largest_observed_value: T::zero(),
         }
     }
 
-    fn max_register_value(&self) -> i32 {
-        *self.registers.values().max().unwrap_or(&0)
+    pub fn max_register_value(&self) -> T {
+        self.registers
+            .values()
+            .cloned()
+            .max()
+            .unwrap_or_else(T::zero)
+    }
+
+    pub fn largest_observed_value(&self) -> T {
+        self.largest_observed_value
+    }
+
+    /// Every register written so far, sorted by name.
+    pub fn sorted(&self) -> Vec<(&str, T)> {
+        let mut registers = self
+            .registers
+            .iter()
+            .map(|(register, &value)| (register.as_str(), value))
+            .collect::<Vec<_>>();
+        registers.sort();
+        registers
     }
 
-    fn get(&self, register: &str) -> i32 {
-        *self.registers.get(register).unwrap_or(&0)
+    /// Registers start out as zero.
+    pub fn get(&self, register: &str) -> T {
+        self.registers
+            .get(register)
+            .cloned()
+            .unwrap_or_else(T::zero)
     }
 
-    fn apply(&mut self, register: &'a str, op: &Operation) {
+    /// Returns the old and the new value of the register.
+    fn apply(&mut self, register: &str, op: &Operation<T>) -> Result<(T, T), ArithmeticError> {
         let value = self.get(register);
-        let new_value = value + op.value();
-        self.registers.insert(register, new_value);
+        let new_value = op.apply(value, op.operand().value(self))?;
+        self.registers.insert(register.to_owned(), new_value);
 
         if new_value > self.largest_observed_value {
             self.largest_observed_value = new_value;
         }
+
+        Ok((value, new_value))
+    }
+
+    fn execute(&mut self, expression: &Expression<T>) -> Result<Option<(T, T)>, ArithmeticError> {
+        if !expression.condition.evaluate(self) {
+            return Ok(None);
+        }
+
+        self.apply(&expression.target_register, &expression.operation)
+            .map(Some)
+    }
+
+    /// Runs the expression and tells whether its condition held.
+    pub fn evaluate(&mut self, expression: &Expression<T>) -> Result<bool, ArithmeticError> {
+        self.execute(expression).map(|change| change.is_some())
+    }
+}
+
+/// A register write, `instruction` counts the expressions executed before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Write<T> {
+    pub instruction: usize,
+    pub register: String,
+    pub old: T,
+    pub new: T,
+}
+
+/// The lowest and highest value a register has held up to and including
+/// `instruction`, counting its initial zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Extent<T> {
+    pub instruction: usize,
+    pub value: T,
+    pub min: T,
+    pub max: T,
+}
+
+/// Runs expressions like `Registers` and logs every write on the way.
+pub struct History<T> {
+    registers: Registers<T>,
+    writes: Vec<Write<T>>,
+    /// Indices into `writes` for every register, in execution order.
+    by_register: Map<String, Vec<usize>>,
+    executed: usize,
+}
+
+impl<T: Integer> Default for History<T> {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+impl<T: Integer> History<T> {
+    pub fn new() -> Self {
+        History {
+            registers: Registers::new(),
+            writes: Vec::new(),
+            by_register: Map::new(),
+            executed: 0,
+        }
     }
 
-    fn evaluate(&mut self, expression: &'a Expression) {
-        let condition_value = self.get(expression.condition.target_register.as_ref());
-        if !expression.condition.evaluate(condition_value) {
-            return;
+    pub fn record(program: &[Expression<T>]) -> Result<Self, ArithmeticError> {
+        let mut history = History::new();
+        for expression in program {
+            history.evaluate(expression)?;
         }
 
-        self.apply(expression.target_register, &expression.operation);
+        Ok(history)
+    }
+
+    pub fn registers(&self) -> &Registers<T> {
+        &self.registers
+    }
+
+    pub fn writes(&self) -> &[Write<T>] {
+        &self.writes
+    }
+
+    /// The number of expressions run so far, fired or not.
+    pub fn executed(&self) -> usize {
+        self.executed
+    }
+
+    /// Runs the expression and tells whether its condition held.
+    pub fn evaluate(&mut self, expression: &Expression<T>) -> Result<bool, ArithmeticError> {
+        let change = self.registers.execute(expression)?;
+        if let Some((old, new)) = change {
+            let register = &expression.target_register;
+            self.by_register
+                .entry(register.clone())
+                .or_default()
+                .push(self.writes.len());
+            self.writes.push(Write {
+                instruction: self.executed,
+                register: register.clone(),
+                old,
+                new,
+            });
+        }
+        self.executed += 1;
+
+        Ok(change.is_some())
+    }
+
+    /// Takes back the last expression run and returns its write, if it
+    /// fired. Returns `None` as well when nothing has run.
+    pub fn undo(&mut self) -> Option<Write<T>> {
+        self.executed = self.executed.checked_sub(1)?;
+        match self.writes.last() {
+            Some(write) if write.instruction == self.executed => {}
+            _ => return None,
+        }
+
+        let write = self.writes.pop()?;
+        let indices = self.by_register.get_mut(&write.register)?;
+        indices.pop();
+        if indices.is_empty() {
+            self.by_register.remove(&write.register);
+            self.registers.registers.remove(&write.register);
+        } else {
+            self.registers
+                .registers
+                .insert(write.register.clone(), write.old);
+        }
+        self.registers.largest_observed_value = self
+            .writes
+            .iter()
+            .map(|write| write.new)
+            .fold(T::zero(), T::max);
+
+        Some(write)
+    }
+
+    fn register_writes<'a>(&'a self, register: &str) -> impl Iterator<Item = &'a Write<T>> + 'a {
+        self.by_register
+            .get(register)
+            .into_iter()
+            .flat_map(move |indices| indices.iter().map(move |&i| &self.writes[i]))
+    }
+
+    /// The value of the register once instruction `k` has run.
+    pub fn value_after(&self, register: &str, k: usize) -> T {
+        let indices = match self.by_register.get(register) {
+            Some(indices) => indices,
+            None => return T::zero(),
+        };
+        let count = indices.partition_point(|&i| self.writes[i].instruction <= k);
+
+        match count {
+            0 => T::zero(),
+            _ => self.writes[indices[count - 1]].new,
+        }
+    }
+
+    /// The first write of the highest value any register has held.
+    pub fn global_maximum(&self) -> Option<&Write<T>> {
+        self.writes
+            .iter()
+            .fold(None, |best: Option<&Write<T>>, write| match best {
+                Some(best) if best.new >= write.new => Some(best),
+                _ => Some(write),
+            })
+    }
+
+    /// Running minimum and maximum of the register after each write to it.
+    pub fn timeline(&self, register: &str) -> Vec<Extent<T>> {
+        let (mut min, mut max) = (T::zero(), T::zero());
+
+        self.register_writes(register)
+            .map(|write| {
+                min = min.min(write.new);
+                max = max.max(write.new);
+                Extent {
+                    instruction: write.instruction,
+                    value: write.new,
+                    min,
+                    max,
+                }
+            })
+            .collect()
+    }
+}
+
+/// What can be told about a program without running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    /// `(read, written)` for every expression that computes the value of
+    /// `written` from `read`, sorted and without duplicates.
+    pub dependencies: Vec<(String, String)>,
+    /// Registers that are written but never read, sorted.
+    pub write_only: Vec<String>,
+    /// `(instruction, outcome)` for every condition that can only go one way.
+    pub constant_conditions: Vec<(usize, bool)>,
+}
+
+impl Analysis {
+    /// Every register whose value may depend on `register`, directly or
+    /// through others, sorted. Includes `register` itself only if its
+    /// value feeds back into it.
+    pub fn dependents(&self, register: &str) -> Vec<&str> {
+        let mut found = Set::new();
+        let mut stack = vec![register];
+
+        while let Some(read) = stack.pop() {
+            for (from, to) in &self.dependencies {
+                if from == read && found.insert(to.as_str()) {
+                    stack.push(to);
+                }
+            }
+        }
+
+        let mut dependents = found.into_iter().collect::<Vec<_>>();
+        dependents.sort();
+        dependents
+    }
+}
+
+pub fn analyse<T: Integer>(program: &[Expression<T>]) -> Analysis {
+    let mut dependencies = Set::new();
+    let mut read = Set::new();
+    let mut written = Set::new();
+    let mut constant_conditions = Vec::new();
+
+    for (i, expression) in program.iter().enumerate() {
+        let target = expression.target_register.as_str();
+        let condition = expression.condition.registers();
+        let operand = expression.operation.operand().register();
+
+        // The straight-line program only sees registers written by earlier
+        // expressions, everything else still holds zero.
+        let outcome = expression
+            .condition
+            .constant(&|register| written.contains(register));
+        if let Some(outcome) = outcome {
+            constant_conditions.push((i, outcome));
+        }
+
+        // A `set` only drops the old value if it is certain to fire.
+        let mut sources = condition.clone();
+        sources.extend(operand);
+        if !matches!(expression.operation, Operation::Set(_)) || outcome != Some(true) {
+            sources.push(target);
+        }
+        for source in sources {
+            dependencies.insert((source.to_owned(), target.to_owned()));
+        }
+
+        read.extend(condition);
+        read.extend(operand);
+        written.insert(target);
+    }
+
+    let mut dependencies = dependencies.into_iter().collect::<Vec<_>>();
+    dependencies.sort();
+    let mut write_only = written
+        .into_iter()
+        .filter(|register| !read.contains(register))
+        .map(|register| register.to_owned())
+        .collect::<Vec<_>>();
+    write_only.sort();
+
+    Analysis {
+        dependencies,
+        write_only,
+        constant_conditions,
     }
 }
 
-fn parse(input: &str) -> Vec<Box<Expression>> {
+pub fn parse<T: Integer>(input: &str) -> Result<Vec<Expression<T>>, ParseError> {
     input
-        .trim()
         .lines()
-        .map(|row| {
-            let tokens = row.split_whitespace().collect::<Vec<_>>();
-            assert!(
-                tokens.len() == 7,
-                format!(
-                    "Expected exactly 7 tokens per expression. Found {} in {:?}",
-                    tokens.len(),
-                    tokens
-                )
-            );
-            let target_register = tokens[0];
-            let operation = Operation::parse(&tokens[1..3]).unwrap();
-            let condition = Condition::parse(&tokens[4..7]).unwrap();
-
-            Box::new(Expression::new(target_register, operation, condition))
-        }).collect()
-}
-
-pub fn solve(input: &str) -> (i32, i32) {
-    let program = parse(input);
+        .enumerate()
+        .filter(|&(_, row)| !row.trim().is_empty())
+        .map(|(i, row)| {
+            Expression::parse(row).map_err(|message| ParseError {
+                line: i + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    Parse(ParseError),
+    Arithmetic(ArithmeticError),
+}
+
+impl From<ParseError> for SolveError {
+    fn from(error: ParseError) -> Self {
+        SolveError::Parse(error)
+    }
+}
+
+impl From<ArithmeticError> for SolveError {
+    fn from(error: ArithmeticError) -> Self {
+        SolveError::Arithmetic(error)
+    }
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SolveError::Parse(ref error) => write!(f, "{}", error),
+            SolveError::Arithmetic(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+pub fn solve<T: Integer>(input: &str) -> Result<(T, T), SolveError> {
+    let program = parse(input)?;
     let mut registers = Registers::new();
 
-    for expression in program.iter() {
-        registers.evaluate(expression);
+    for expression in &program {
+        registers.evaluate(expression)?;
     }
 
-    (
+    Ok((
         registers.max_register_value(),
         registers.largest_observed_value,
-    )
+    ))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
 
     #[test]
     fn test_cases_star_one() {
         assert_eq!(
-            solve(
+            solve::<i32>(
                 "
         b inc 5 if a > 1
         a inc 1 if b < 5
@@ -217,8 +841,235 @@ mod tests {
         c inc -20 if c == 10
             "
             ),
-            (1, 10)
+            Ok((1, 10))
         );
     }
 
+    #[test]
+    fn test_overflow() {
+        let input = "
+        a inc 2147483647 if b == 0
+        a inc 1 if b == 0
+        ";
+
+        assert_eq!(
+            solve::<i32>(input),
+            Err(SolveError::Arithmetic(ArithmeticError::Overflow))
+        );
+        assert_eq!(solve::<i64>(input), Ok((2147483648, 2147483648)));
+    }
+
+    #[test]
+    fn test_solve_parse_error() {
+        assert_eq!(
+            solve::<i64>("a inc 1 if b > 1\na pow 2 if b > 1"),
+            Err(SolveError::Parse(ParseError {
+                line: 2,
+                message: "Unknown operation \"pow\"".to_owned(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse::<i32>("a inc 1 if b").is_err());
+        assert!(parse::<i32>("a inc 1 when b > 1").is_err());
+        assert!(parse::<i32>("a pow 1 if b > 1").is_err());
+        assert!(parse::<i32>("a inc 1 if b <> 1").is_err());
+        assert!(parse::<i32>("a inc 1 if b > 1x").is_err());
+        assert!(parse::<i32>("a inc 1 if b > x").is_ok());
+        assert!(parse::<i32>("a inc 99999999999 if b > 1").is_err());
+        assert!(parse::<i32>("1a inc 1 if b > 1").is_err());
+        assert!(parse::<i32>("a inc 1 if b > 1 and").is_err());
+        assert!(parse::<i32>("a inc 1 if (b > 1").is_err());
+        assert!(parse::<i32>("a inc 1 if b > 1)").is_err());
+        assert!(parse::<i32>("a inc 1 if b > 1 c").is_err());
+        assert!(parse::<i32>("a inc 1 if not > 1").is_err());
+        assert_eq!(
+            parse::<i32>("a inc 1 if b > 1\n\nc pow 2 if d < 1"),
+            Err(ParseError {
+                line: 3,
+                message: "Unknown operation \"pow\"".to_owned(),
+            })
+        );
+
+        let deep = format!("a inc 1 if {}b > 1", "not ".repeat(100));
+        assert_eq!(
+            parse::<i32>(&deep).unwrap_err().message,
+            "Condition nested too deeply"
+        );
+    }
+
+    #[test]
+    fn test_operations() {
+        let input = "
+        a set 7 if x == 0
+        b set a if a > 0
+        b mul 6 if b == 7
+        c set b if c == 0
+        c div 4 if c != 0
+        d set b if b >= 42
+        d mod -5 if d <= a
+        d mod 5 if d > a
+        e dec -3 if a < b
+        ";
+        let program = parse::<i64>(input).unwrap();
+        let mut registers = Registers::new();
+        for expression in &program {
+            registers.evaluate(expression).unwrap();
+        }
+
+        assert_eq!(registers.get("a"), 7);
+        assert_eq!(registers.get("b"), 42);
+        assert_eq!(registers.get("c"), 10);
+        assert_eq!(registers.get("d"), 2);
+        assert_eq!(registers.get("e"), 3);
+        assert_eq!(registers.largest_observed_value(), 42);
+
+        assert_eq!(
+            solve::<i64>("a div b if a == 0"),
+            Err(SolveError::Arithmetic(ArithmeticError::DivisionByZero))
+        );
+        assert_eq!(
+            solve::<i64>("a mod 0 if a == 0"),
+            Err(SolveError::Arithmetic(ArithmeticError::DivisionByZero))
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let input = "
+        b inc 5 if a > 1
+        a inc 1 if b < 5
+        c dec -10 if a >= 1
+        c inc -20 if c == 10
+        a set 7 if c < 0
+        c mul 3 if a == 7
+        ";
+        let program = parse::<i64>(input).unwrap();
+        let history = History::record(&program).unwrap();
+
+        assert_eq!(history.executed(), 6);
+        assert_eq!(history.writes().len(), 5);
+        assert_eq!(
+            history.writes()[1],
+            Write {
+                instruction: 2,
+                register: "c".to_owned(),
+                old: 0,
+                new: 10,
+            }
+        );
+
+        assert_eq!(history.value_after("c", 1), 0);
+        assert_eq!(history.value_after("c", 2), 10);
+        assert_eq!(history.value_after("c", 4), -10);
+        assert_eq!(history.value_after("c", 99), -30);
+        assert_eq!(history.value_after("z", 3), 0);
+        assert_eq!(history.registers().get("a"), 7);
+
+        let maximum = history.global_maximum().unwrap();
+        assert_eq!((maximum.instruction, maximum.new), (2, 10));
+
+        let extents = history
+            .timeline("c")
+            .iter()
+            .map(|extent| (extent.instruction, extent.value, extent.min, extent.max))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            extents,
+            vec![(2, 10, 0, 10), (3, -10, -10, 10), (5, -30, -30, 10)]
+        );
+        assert!(history.timeline("b").is_empty());
+        assert!(History::<i64>::new().global_maximum().is_none());
+
+        let mut history = history;
+        assert_eq!(history.undo().map(|write| write.new), Some(-30));
+        assert_eq!(history.registers().get("c"), -10);
+        assert_eq!(
+            history.undo().map(|write| write.register),
+            Some("a".to_owned())
+        );
+        assert_eq!(history.undo().map(|write| write.old), Some(10));
+        assert_eq!(history.undo().map(|write| write.new), Some(10));
+        assert_eq!(history.registers().sorted(), vec![("a", 1)]);
+        assert_eq!(history.registers().largest_observed_value(), 1);
+        assert_eq!(
+            history.undo().map(|write| write.register),
+            Some("a".to_owned())
+        );
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.executed(), 0);
+        assert!(history.registers().sorted().is_empty());
+    }
+
+    #[test]
+    fn test_analyse() {
+        let input = "
+        a inc 1 if z == 0
+        b set a if a > 0
+        c inc b if b < 0 or z != 0
+        a dec 1 if a != a
+        d inc 1 if x == 0 and c > 0
+        e set 5 if not (z > 0 and c > 0)
+        ";
+        let program = parse::<i64>(input).unwrap();
+        let analysis = analyse(&program);
+        let pair = |read: &str, written: &str| (read.to_owned(), written.to_owned());
+
+        assert_eq!(
+            analysis.dependencies,
+            vec![
+                pair("a", "a"),
+                pair("a", "b"),
+                pair("b", "b"),
+                pair("b", "c"),
+                pair("c", "c"),
+                pair("c", "d"),
+                pair("c", "e"),
+                pair("d", "d"),
+                pair("x", "d"),
+                pair("z", "a"),
+                pair("z", "c"),
+                pair("z", "e"),
+            ]
+        );
+        assert_eq!(analysis.write_only, vec!["d".to_owned(), "e".to_owned()]);
+        assert_eq!(
+            analysis.constant_conditions,
+            vec![(0, true), (3, false), (5, true)]
+        );
+        assert_eq!(analysis.dependents("a"), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(analysis.dependents("b"), vec!["b", "c", "d", "e"]);
+        assert_eq!(analysis.dependents("e"), Vec::<&str>::new());
+        assert_eq!(analysis.dependents("x"), vec!["d"]);
+    }
+
+    #[test]
+    fn test_combined_conditions() {
+        let input = "
+        a inc 1 if b == 0 and c == 0
+        b inc 1 if not a == 1 or c > 0
+        c inc 1 if not (a == 1 and b == 0)
+        d inc 1 if (a == 0 or b == 0) and not(c == 1)
+        e inc 1 if a == 1 or b == 1 and c == 1
+        ";
+        let program = parse::<i64>(input).unwrap();
+        let mut registers = Registers::new();
+        let fired = program
+            .iter()
+            .map(|expression| registers.evaluate(expression).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(fired, vec![true, false, false, true, true]);
+        assert_eq!(
+            program[4].to_string(),
+            "e inc 1 if (a == 1 or (b == 1 and c == 1))"
+        );
+        assert_eq!(
+            program[3].to_string(),
+            "d inc 1 if ((a == 0 or b == 0) and not c == 1)"
+        );
+    }
 }
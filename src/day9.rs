@@ -1,89 +1,448 @@
-#[derive(Debug)]
-struct State {
-    depth: i32,
-    score: i32,
-    ignore: bool,
-    in_garbage: bool,
-    garbage_count: i32,
+use core::fmt;
+use core::str::CharIndices;
+use prelude::*;
+
+/// Byte offsets into the stream, `end` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
-impl State {
-    fn new() -> Self {
-        State {
-            depth: 0,
-            score: 0,
-            ignore: false,
-            in_garbage: false,
-            garbage_count: 0,
-        }
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The outermost group is at depth 1, which is also its score.
+    GroupOpen {
+        position: usize,
+        depth: usize,
+    },
+    GroupClose {
+        span: Span,
+        depth: usize,
+    },
+    /// Emitted once the garbage is closed, after its cancelled characters.
+    /// `characters` leaves out the delimiters and anything cancelled.
+    Garbage {
+        span: Span,
+        characters: usize,
+    },
+    /// A character inside garbage following a `!`.
+    Cancelled {
+        position: usize,
+        character: char,
+    },
 }
 
-fn change(score: i32, in_garbage: bool, amount: i32) -> i32 {
-    if in_garbage {
-        return score;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxError {
+    Empty,
+    Unexpected { position: usize, found: char },
+    UnmatchedClose { position: usize },
+    UnclosedGroup { start: usize },
+    UnclosedGarbage { start: usize },
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyntaxError::Empty => write!(f, "Expected a group, found nothing"),
+            SyntaxError::Unexpected { position, found } => {
+                write!(f, "Unexpected {:?} at position {}", found, position)
+            }
+            SyntaxError::UnmatchedClose { position } => {
+                write!(f, "Unmatched `}}` at position {}", position)
+            }
+            SyntaxError::UnclosedGroup { start } => {
+                write!(f, "Group opened at position {} is never closed", start)
+            }
+            SyntaxError::UnclosedGarbage { start } => {
+                write!(f, "Garbage opened at position {} is never closed", start)
+            }
+        }
     }
+}
 
-    score + amount
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Expect {
+    Root,
+    /// Right after `{`, so the group may also close.
+    Item,
+    ItemAfterComma,
+    Separator,
+    End,
 }
 
-pub fn solve(input: &str) -> (i32, i32) {
-    let mut state = State::new();
+/// Streams the events of a single top-level group. Stops after the first
+/// syntax error.
+pub struct Events<'a> {
+    chars: CharIndices<'a>,
+    /// Positions of the open groups, innermost last.
+    open: Vec<usize>,
+    /// Start and character count of the garbage being read.
+    garbage: Option<(usize, usize)>,
+    cancel_next: bool,
+    expect: Expect,
+    done: bool,
+}
 
-    input.chars().for_each(|token| {
-        let mut should_reset_ignore = state.ignore;
+impl<'a> Events<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Events {
+            chars: input.char_indices(),
+            open: Vec::new(),
+            garbage: None,
+            cancel_next: false,
+            expect: Expect::Root,
+            done: false,
+        }
+    }
 
-        if !state.ignore && state.in_garbage && token != '>' && token != '!' {
-            state.garbage_count += 1;
+    fn after_item(&self) -> Expect {
+        if self.open.is_empty() {
+            Expect::End
+        } else {
+            Expect::Separator
         }
+    }
 
-        match token {
-            '{' => state.depth = change(state.depth, state.in_garbage, 1),
-            '}' => {
-                state.score = change(state.score, state.in_garbage, state.depth);
-                state.depth = change(state.depth, state.in_garbage, -1);
-            }
-            '<' => {
-                if !state.ignore && !state.in_garbage {
-                    state.in_garbage = true;
+    fn advance(&mut self) -> Option<Result<Event, SyntaxError>> {
+        while let Some((position, c)) = self.chars.next() {
+            if let Some((start, characters)) = self.garbage {
+                if self.cancel_next {
+                    self.cancel_next = false;
+                    return Some(Ok(Event::Cancelled {
+                        position,
+                        character: c,
+                    }));
                 }
-            }
-            '>' => {
-                if !state.ignore && state.in_garbage {
-                    state.in_garbage = false
+
+                match c {
+                    '!' => self.cancel_next = true,
+                    '>' => {
+                        self.garbage = None;
+                        self.expect = self.after_item();
+                        return Some(Ok(Event::Garbage {
+                            span: Span {
+                                start,
+                                end: position + 1,
+                            },
+                            characters,
+                        }));
+                    }
+                    _ => self.garbage = Some((start, characters + 1)),
                 }
+                continue;
             }
-            '!' => {
-                if !state.ignore {
-                    state.ignore = true;
-                    should_reset_ignore = false;
+
+            match (self.expect, c) {
+                (Expect::Root, c) | (Expect::End, c) if c.is_whitespace() => {}
+                (Expect::Root, '{') | (Expect::Item, '{') | (Expect::ItemAfterComma, '{') => {
+                    self.open.push(position);
+                    self.expect = Expect::Item;
+                    return Some(Ok(Event::GroupOpen {
+                        position,
+                        depth: self.open.len(),
+                    }));
+                }
+                (Expect::Item, '<') | (Expect::ItemAfterComma, '<') => {
+                    self.garbage = Some((position, 0));
+                }
+                (Expect::Item, '}') | (Expect::Separator, '}') => {
+                    let depth = self.open.len();
+                    let start = self.open.pop().expect("Group is open");
+                    self.expect = self.after_item();
+                    return Some(Ok(Event::GroupClose {
+                        span: Span {
+                            start,
+                            end: position + 1,
+                        },
+                        depth,
+                    }));
                 }
+                (Expect::Separator, ',') => self.expect = Expect::ItemAfterComma,
+                (_, '}') if self.open.is_empty() => {
+                    return Some(Err(SyntaxError::UnmatchedClose { position }))
+                }
+                (_, found) => return Some(Err(SyntaxError::Unexpected { position, found })),
             }
-            ',' => {}
-            _ => {}
         }
 
-        if should_reset_ignore {
-            state.ignore = false;
+        if let Some((start, _)) = self.garbage {
+            return Some(Err(SyntaxError::UnclosedGarbage { start }));
+        }
+        if let Some(&start) = self.open.last() {
+            return Some(Err(SyntaxError::UnclosedGroup { start }));
+        }
+        if self.expect == Expect::Root {
+            return Some(Err(SyntaxError::Empty));
+        }
+
+        None
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event, SyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let event = self.advance();
+        self.done = !matches!(event, Some(Ok(_)));
+
+        event
+    }
+}
+
+/// Index of a node in its stream's arena.
+pub type NodeId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Group,
+    Garbage { characters: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub kind: Kind,
+    pub span: Span,
+    pub children: Vec<NodeId>,
+}
+
+/// The groups and garbage of a stream, the outermost group is node 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stream {
+    nodes: Vec<Node>,
+}
+
+impl Stream {
+    pub fn root(&self) -> &Node {
+        &self.nodes[0]
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// The sum of the depths of every group.
+    pub fn score(&self) -> u64 {
+        let mut score = 0;
+        let mut stack = vec![(0, 1)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.nodes[id];
+            if node.kind == Kind::Group {
+                score += depth;
+                stack.extend(node.children.iter().map(|&child| (child, depth + 1)));
+            }
+        }
+
+        score
+    }
+
+    /// Characters inside garbage, without the cancelled ones.
+    pub fn garbage(&self) -> u64 {
+        self.nodes
+            .iter()
+            .map(|node| match node.kind {
+                Kind::Garbage { characters } => characters as u64,
+                Kind::Group => 0,
+            })
+            .sum()
+    }
+}
+
+pub fn parse(input: &str) -> Result<Stream, SyntaxError> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut open: Vec<NodeId> = Vec::new();
+
+    for event in Events::new(input) {
+        let (kind, span) = match event? {
+            Event::GroupOpen { position, .. } => (
+                Kind::Group,
+                Span {
+                    start: position,
+                    end: position,
+                },
+            ),
+            Event::GroupClose { span, .. } => {
+                let id = open.pop().expect("Group is open");
+                nodes[id].span = span;
+                continue;
+            }
+            Event::Garbage { span, characters } => (Kind::Garbage { characters }, span),
+            Event::Cancelled { .. } => continue,
+        };
+
+        let id = nodes.len();
+        if let Some(&parent) = open.last() {
+            nodes[parent].children.push(id);
+        }
+        if kind == Kind::Group {
+            open.push(id);
+        }
+        nodes.push(Node {
+            kind,
+            span,
+            children: Vec::new(),
+        });
+    }
+
+    Ok(Stream { nodes })
+}
+
+/// The score of all groups and the number of garbage characters, straight
+/// from the events without building the tree.
+pub fn solve(input: &str) -> Result<(u64, u64), SyntaxError> {
+    let mut score = 0;
+    let mut garbage = 0;
+
+    for event in Events::new(input) {
+        match event? {
+            Event::GroupOpen { depth, .. } => score += depth as u64,
+            Event::Garbage { characters, .. } => garbage += characters as u64,
+            _ => {}
         }
-    });
+    }
 
-    (state.score, state.garbage_count)
+    Ok((score, garbage))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
 
     #[test]
     fn test_cases_star_one() {
-        assert_eq!(solve("{}"), (1, 0));
-        assert_eq!(solve("{{{}}}"), (6, 0));
-        assert_eq!(solve("{{},{}}"), (5, 0));
-        assert_eq!(solve("{{{},{},{{}}}}"), (16, 0));
-        assert_eq!(solve("{<a>,<a>,<a>,<a>}"), (1, 4));
-        assert_eq!(solve("{{<ab>},{<ab>},{<ab>},{<ab>}}"), (9, 8));
-        assert_eq!(solve("{{<!!>},{<!!>},{<!!>},{<!!>}}"), (9, 0));
-        assert_eq!(solve("{{<a!>},{<a!>},{<a!>},{<ab>}}"), (3, 17));
+        assert_eq!(solve("{}"), Ok((1, 0)));
+        assert_eq!(solve("{{{}}}"), Ok((6, 0)));
+        assert_eq!(solve("{{},{}}"), Ok((5, 0)));
+        assert_eq!(solve("{{{},{},{{}}}}"), Ok((16, 0)));
+        assert_eq!(solve("{<a>,<a>,<a>,<a>}"), Ok((1, 4)));
+        assert_eq!(solve("{{<ab>},{<ab>},{<ab>},{<ab>}}"), Ok((9, 8)));
+        assert_eq!(solve("{{<!!>},{<!!>},{<!!>},{<!!>}}"), Ok((9, 0)));
+        assert_eq!(solve("{{<a!>},{<a!>},{<a!>},{<ab>}}"), Ok((3, 17)));
+    }
+
+    #[test]
+    fn test_events() {
+        let events = Events::new("{<a!>>,{}}\n").collect::<Result<Vec<_>, _>>();
+        let span = |start, end| Span { start, end };
+
+        assert_eq!(
+            events,
+            Ok(vec![
+                Event::GroupOpen {
+                    position: 0,
+                    depth: 1,
+                },
+                Event::Cancelled {
+                    position: 4,
+                    character: '>',
+                },
+                Event::Garbage {
+                    span: span(1, 6),
+                    characters: 1,
+                },
+                Event::GroupOpen {
+                    position: 7,
+                    depth: 2,
+                },
+                Event::GroupClose {
+                    span: span(7, 9),
+                    depth: 2,
+                },
+                Event::GroupClose {
+                    span: span(0, 10),
+                    depth: 1,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let stream = parse("{{<ab>},{},<!x>}").unwrap();
+        let root = stream.root();
+
+        assert_eq!(root.span, Span { start: 0, end: 16 });
+        assert_eq!(root.children.len(), 3);
+        let first = stream.node(root.children[0]);
+        assert_eq!(first.span, Span { start: 1, end: 7 });
+        assert_eq!(
+            stream.node(first.children[0]).kind,
+            Kind::Garbage { characters: 2 }
+        );
+        assert_eq!(stream.node(root.children[1]).kind, Kind::Group);
+        assert_eq!(
+            stream.node(root.children[2]).span,
+            Span { start: 11, end: 15 }
+        );
+        assert_eq!((stream.score(), stream.garbage()), (5, 2));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(solve(""), Err(SyntaxError::Empty));
+        assert_eq!(solve(" \n"), Err(SyntaxError::Empty));
+        assert_eq!(solve("{{}"), Err(SyntaxError::UnclosedGroup { start: 0 }));
+        assert_eq!(
+            solve("{{<a}"),
+            Err(SyntaxError::UnclosedGarbage { start: 2 })
+        );
+        assert_eq!(
+            solve("{}}"),
+            Err(SyntaxError::UnmatchedClose { position: 2 })
+        );
+        assert_eq!(
+            solve("{},{}"),
+            Err(SyntaxError::Unexpected {
+                position: 2,
+                found: ',',
+            })
+        );
+        assert_eq!(
+            solve("{a}"),
+            Err(SyntaxError::Unexpected {
+                position: 1,
+                found: 'a',
+            })
+        );
+        assert_eq!(
+            solve("{{},}"),
+            Err(SyntaxError::Unexpected {
+                position: 4,
+                found: '}',
+            })
+        );
+        assert_eq!(
+            solve("{{}{}}"),
+            Err(SyntaxError::Unexpected {
+                position: 3,
+                found: '{',
+            })
+        );
+        assert_eq!(
+            solve("<a>"),
+            Err(SyntaxError::Unexpected {
+                position: 0,
+                found: '<',
+            })
+        );
+        assert!(parse("{<!>}").is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 1_000_000;
+        let input = "{".repeat(depth) + &"}".repeat(depth);
+        let stream = parse(&input).unwrap();
+        let score = (depth * (depth + 1) / 2) as u64;
+
+        assert_eq!(stream.score(), score);
+        assert_eq!(solve(&input), Ok((score, 0)));
     }
 }
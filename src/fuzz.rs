@@ -0,0 +1,563 @@
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use day1;
+use day10;
+use day11;
+use day12;
+use day13;
+use day16;
+use day18;
+use day19;
+use day2;
+use day20;
+use day4;
+use day5;
+use day6;
+use day7;
+use day8;
+use day9;
+use registry::Input;
+
+pub const REGRESSIONS_DIR: &str = "fuzz/regressions";
+
+/// Inputs larger than this are truncated, it keeps each execution fast
+/// without losing the structure of the real puzzle inputs.
+const MAX_INPUT_SIZE: usize = 64 * 1024;
+const MAX_CORPUS_SIZE: usize = 64;
+
+const INTERESTING_BYTES: &[u8] = &[
+    b'\n', b' ', b',', b'/', b'-', b'+', b'<', b'>', b'(', b')', b':', b'=', b'!', b'{', b'}',
+    b'0', b'9', 0, 0x7f, 0xc3, 0xff,
+];
+
+const INTERESTING_TOKENS: &[&str] = &[
+    "<->",
+    "->",
+    " if ",
+    "\n\n",
+    "é",
+    "-",
+    "99999999999999999999",
+    "-9223372036854775808",
+    "4294967296",
+];
+
+pub struct Target {
+    pub name: &'static str,
+    seeds: Vec<Input>,
+    parse: fn(&str) -> Result<(), String>,
+}
+
+impl Target {
+    fn new(name: &'static str, seeds: Vec<Input>, parse: fn(&str) -> Result<(), String>) -> Self {
+        Target { name, seeds, parse }
+    }
+}
+
+pub fn targets() -> Vec<Target> {
+    vec![
+        Target::new(
+            "day1",
+            vec![Input::File("day1.txt"), Input::Inline("91212129")],
+            |input| {
+                let _ = day1::parse(input, day1::Alphabet::Radix(16));
+                let _ = day1::parse(input, day1::Alphabet::Letters);
+                let _ = day1::solve_with(input, day1::Alphabet::Tokens(','), 1);
+                day1::solve(input, 1).map(|_| ())
+            },
+        ),
+        Target::new(
+            "day2",
+            vec![Input::File("day2.txt"), Input::Inline("a,b,c\n5,1,9\n7,,3")],
+            |input| {
+                for &format in &[
+                    day2::Format::Csv,
+                    day2::Format::Tsv,
+                    day2::Format::Whitespace,
+                ] {
+                    let options = day2::Options {
+                        format,
+                        header: true,
+                        empty_cells: day2::EmptyCells::Zero,
+                    };
+                    if let Ok(sheet) = day2::Spreadsheet::parse(input, &options) {
+                        let _ = sheet
+                            .checksum(day2::Orientation::Columns, &day2::row_data_evenly_divisible);
+                    }
+                }
+
+                day2::Spreadsheet::parse(input, &day2::Options::default())
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            },
+        ),
+        Target::new(
+            "day4",
+            vec![Input::File("day4.txt"), Input::Inline("aa bb cc\naa bb aa")],
+            |input| {
+                day4::parse(input);
+                Ok(())
+            },
+        ),
+        Target::new(
+            "day4-rules",
+            vec![
+                Input::Inline("all(unique-ignore-case, min-words(3), not(deny(a, b)))"),
+                Input::Inline("any(no-anagrams, min-distance(2), dictionary(aa, bb))"),
+            ],
+            |input| day4::Rule::parse(input).map(|_| ()),
+        ),
+        Target::new(
+            "day5",
+            vec![Input::File("day5.txt"), Input::Inline("0 3 0 1 -3")],
+            |input| day5::parse::<i32>(input).map(|_| ()),
+        ),
+        Target::new(
+            "day6",
+            vec![Input::File("day6.txt"), Input::Inline("0 2 7 0")],
+            |input| day6::solve(input).map(|_| ()),
+        ),
+        Target::new(
+            "day7",
+            vec![
+                Input::File("day7.txt"),
+                Input::Inline("pbga (66)\nfwft (72) -> pbga, cntj\ncntj (57)"),
+            ],
+            |input| {
+                day7::parse(input)
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            },
+        ),
+        Target::new(
+            "day8",
+            vec![
+                Input::File("day8.txt"),
+                Input::Inline("b inc 5 if a > 1\nc dec -10 if a >= 1"),
+            ],
+            |input| {
+                day8::parse::<i32>(input)
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            },
+        ),
+        Target::new(
+            "day9",
+            vec![Input::File("day9.txt"), Input::Inline("{{<a!>},{<ab>}}")],
+            |input| {
+                day9::parse(input)
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            },
+        ),
+        Target::new(
+            "day10",
+            vec![Input::Inline(
+                "106,16,254,226,55,2,1,166,177,247,93,0,255,228,60,36",
+            )],
+            |input| day10::parse(input, 256).map(|_| ()),
+        ),
+        Target::new(
+            "day11",
+            vec![Input::File("day11.txt"), Input::Inline("ne,ne,s,s")],
+            |input| day11::parse(input).map(|_| ()),
+        ),
+        Target::new(
+            "day12",
+            vec![
+                Input::File("day12.txt"),
+                Input::Inline("0 <-> 2\n2 <-> 0, 3"),
+            ],
+            |input| day12::parse(input).map(|_| ()),
+        ),
+        Target::new(
+            "day13",
+            vec![Input::File("day13.txt"), Input::Inline("0: 3\n1: 2\n4: 4")],
+            |input| day13::parse(input).map(|_| ()),
+        ),
+        Target::new(
+            "day16",
+            vec![Input::File("day16.txt"), Input::Inline("s1,x3/4,pe/b")],
+            |input| day16::solve(input, 16, 1).map(|_| ()),
+        ),
+        Target::new(
+            "day18",
+            vec![
+                Input::File("day18.txt"),
+                Input::Inline("set a 1\nadd a 2\nsnd a\nrcv a\njgz a -1"),
+            ],
+            |input| day18::parse::<i64>(input).map(|_| ()),
+        ),
+        Target::new(
+            "day19",
+            vec![
+                Input::File("day19.txt"),
+                Input::Inline("  |\n  A\n  +-B-+\n      |\n      C"),
+            ],
+            |input| day19::solve(input).map(|_| ()),
+        ),
+        Target::new(
+            "day20",
+            vec![
+                Input::File("day20.txt"),
+                Input::Inline("p=< 3,0,0>, v=< 2,0,0>, a=<-1,0,0>"),
+            ],
+            |input| day20::parse(input).map(|_| ()),
+        ),
+    ]
+}
+
+/// Xorshift64* generator, good enough to drive mutations and keeps runs
+/// reproducible from a seed.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng {
+            state: seed ^ 0x9e37_79b9_7f4a_7c15 | 1,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            return 0;
+        }
+
+        (self.next() % bound as u64) as usize
+    }
+}
+
+fn mutate(data: &mut Vec<u8>, corpus: &[Vec<u8>], rng: &mut Rng) {
+    let len = data.len();
+
+    match rng.below(8) {
+        0 if len > 0 => {
+            let index = rng.below(len);
+            data[index] ^= 1 << rng.below(8);
+        }
+        1 if len > 0 => {
+            let index = rng.below(len);
+            data[index] = INTERESTING_BYTES[rng.below(INTERESTING_BYTES.len())];
+        }
+        2 => {
+            let index = rng.below(len + 1);
+            data.insert(index, rng.next() as u8);
+        }
+        3 if len > 0 => {
+            let start = rng.below(len);
+            let end = start + rng.below((len - start).min(16)) + 1;
+            data.drain(start..end);
+        }
+        4 if len > 0 => {
+            let start = rng.below(len);
+            let end = start + rng.below((len - start).min(64)) + 1;
+            let chunk = data[start..end].to_vec();
+            let index = rng.below(len + 1);
+            data.splice(index..index, chunk);
+        }
+        5 => {
+            let new_len = rng.below(len + 1);
+            data.truncate(new_len);
+        }
+        6 if !corpus.is_empty() => {
+            let other = &corpus[rng.below(corpus.len())];
+            if !other.is_empty() {
+                let start = rng.below(other.len());
+                let end = start + rng.below((other.len() - start).min(64)) + 1;
+                let index = rng.below(len + 1);
+                data.splice(index..index, other[start..end].iter().cloned());
+            }
+        }
+        _ => {
+            let token = INTERESTING_TOKENS[rng.below(INTERESTING_TOKENS.len())];
+            let index = rng.below(len + 1);
+            data.splice(index..index, token.bytes());
+        }
+    }
+
+    data.truncate(MAX_INPUT_SIZE);
+}
+
+fn hash(data: &[u8]) -> u64 {
+    // FNV-1a
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |acc, &byte| {
+        (acc ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_owned()
+    }
+}
+
+/// Runs `target` on `input`, returning the panic message if it panicked.
+fn execute(target: &Target, input: &str) -> Result<bool, String> {
+    panic::catch_unwind(AssertUnwindSafe(|| (target.parse)(input).is_ok()))
+        .map_err(|payload| panic_message(&*payload))
+}
+
+thread_local!(static SILENCED: Cell<bool> = const { Cell::new(false) });
+static INSTALL_HOOK: Once = Once::new();
+
+/// Suppresses panic messages on the current thread while `closure` runs,
+/// other threads keep reporting panics as usual.
+fn with_silenced_panics<F, T>(closure: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    INSTALL_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !SILENCED.with(|silenced| silenced.get()) {
+                default_hook(info);
+            }
+        }));
+    });
+
+    SILENCED.with(|silenced| silenced.set(true));
+    let result = closure();
+    SILENCED.with(|silenced| silenced.set(false));
+
+    result
+}
+
+#[derive(Debug)]
+pub struct Crash {
+    pub target: &'static str,
+    pub input: String,
+    pub message: String,
+}
+
+impl Crash {
+    fn file_name(&self) -> String {
+        format!("{}-{:016x}.txt", self.target, hash(self.input.as_bytes()))
+    }
+
+    pub fn save(&self, directory: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(self.file_name());
+        File::create(&path)?.write_all(self.input.as_bytes())?;
+
+        Ok(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub executions: usize,
+    pub crashes: Vec<Crash>,
+}
+
+pub fn run(targets: &[Target], iterations: usize, seed: u64) -> Report {
+    let mut rng = Rng::new(seed);
+    let mut corpora = targets
+        .iter()
+        .map(|target| {
+            target
+                .seeds
+                .iter()
+                .filter_map(|seed| seed.load().ok())
+                .map(|seed| seed.into_bytes())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut report = Report {
+        executions: 0,
+        crashes: Vec::new(),
+    };
+
+    if targets.is_empty() {
+        return report;
+    }
+
+    with_silenced_panics(|| {
+        for _ in 0..iterations {
+            let index = rng.below(targets.len());
+            let target = &targets[index];
+            let corpus = &mut corpora[index];
+
+            let mut data = if corpus.is_empty() {
+                Vec::new()
+            } else {
+                corpus[rng.below(corpus.len())].clone()
+            };
+            for _ in 0..rng.below(4) + 1 {
+                mutate(&mut data, corpus, &mut rng);
+            }
+
+            let input = String::from_utf8_lossy(&data).into_owned();
+            report.executions += 1;
+
+            match execute(target, &input) {
+                Ok(true) if corpus.len() < MAX_CORPUS_SIZE => corpus.push(data),
+                Ok(_) => {}
+                Err(message) => {
+                    let is_known = report
+                        .crashes
+                        .iter()
+                        .any(|crash| crash.target == target.name && crash.message == message);
+                    if !is_known {
+                        report.crashes.push(Crash {
+                            target: target.name,
+                            input,
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+    });
+
+    report
+}
+
+/// Replays every saved regression case in `directory`. Files are named
+/// `<target>-<hash>.txt`.
+pub fn replay(targets: &[Target], directory: &Path) -> io::Result<Vec<Crash>> {
+    let mut crashes = Vec::new();
+    if !directory.exists() {
+        return Ok(crashes);
+    }
+
+    let mut paths = fs::read_dir(directory)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    with_silenced_panics(|| -> io::Result<()> {
+        for path in paths {
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("")
+                .to_owned();
+            // Target names may contain `-` themselves, the hash never does.
+            let target_name = file_name.rsplit_once('-').map_or("", |(name, _)| name);
+            let target = match targets.iter().find(|target| target.name == target_name) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let mut input = String::new();
+            File::open(&path)?.read_to_string(&mut input)?;
+
+            if let Err(message) = execute(target, &input) {
+                crashes.push(Crash {
+                    target: target.name,
+                    input,
+                    message,
+                });
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(crashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A directory unique to this process and call, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+
+            TempDir(std::env::temp_dir().join(format!("{}-{}-{}", name, process::id(), count)))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_mutate_is_deterministic() {
+        let corpus = vec![b"0 2 7 0".to_vec()];
+        let mut a = b"0 3 0 1 -3".to_vec();
+        let mut b = a.clone();
+        let mut rng_a = Rng::new(7);
+        let mut rng_b = Rng::new(7);
+
+        for _ in 0..100 {
+            mutate(&mut a, &corpus, &mut rng_a);
+            mutate(&mut b, &corpus, &mut rng_b);
+        }
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_detects_panics() {
+        let targets = vec![Target::new("panics", vec![Input::Inline("x")], |_| {
+            panic!("boom")
+        })];
+        let report = run(&targets, 10, 1);
+
+        assert_eq!(report.executions, 10);
+        assert_eq!(report.crashes.len(), 1);
+        assert_eq!(report.crashes[0].message, "boom");
+    }
+
+    #[test]
+    fn test_replay_detects_panics() {
+        let directory = TempDir::new("aoc-fuzz-replay");
+        let targets = vec![
+            Target::new("panics-here", vec![], |_| panic!("boom")),
+            Target::new("panics", vec![], |_| Ok(())),
+        ];
+        let crash = Crash {
+            target: "panics-here",
+            input: "x".to_owned(),
+            message: "boom".to_owned(),
+        };
+        crash.save(&directory.0).unwrap();
+
+        let crashes = replay(&targets, &directory.0).unwrap();
+
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].input, "x");
+    }
+
+    #[test]
+    fn test_parsers_do_not_panic() {
+        let report = run(&targets(), 2_000, 2017);
+
+        assert!(report.crashes.is_empty(), "{:?}", report.crashes);
+    }
+
+    #[test]
+    fn test_regressions() {
+        let crashes = replay(&targets(), Path::new(REGRESSIONS_DIR)).unwrap();
+
+        assert!(crashes.is_empty(), "{:?}", crashes);
+    }
+}
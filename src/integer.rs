@@ -0,0 +1,124 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::num::ParseIntError;
+use core::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArithmeticError {
+    Overflow,
+    DivisionByZero,
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArithmeticError::Overflow => write!(f, "Arithmetic overflow"),
+            ArithmeticError::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+/// Integer types the numeric solvers can be run with. Every operation is
+/// checked and reports overflow as an `ArithmeticError` instead of wrapping.
+pub trait Integer: Copy + Ord + fmt::Debug + fmt::Display + FromStr<Err = ParseIntError> {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_usize(value: usize) -> Option<Self>;
+    fn to_usize(self) -> Option<usize>;
+    fn to_i128(self) -> i128;
+
+    fn try_add(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn try_sub(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn try_mul(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn try_div(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn try_rem(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn try_neg(self) -> Result<Self, ArithmeticError>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Integer for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn from_usize(value: usize) -> Option<Self> {
+                    <$t>::try_from(value).ok()
+                }
+
+                fn to_usize(self) -> Option<usize> {
+                    usize::try_from(self).ok()
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn try_add(self, other: Self) -> Result<Self, ArithmeticError> {
+                    self.checked_add(other).ok_or(ArithmeticError::Overflow)
+                }
+
+                fn try_sub(self, other: Self) -> Result<Self, ArithmeticError> {
+                    self.checked_sub(other).ok_or(ArithmeticError::Overflow)
+                }
+
+                fn try_mul(self, other: Self) -> Result<Self, ArithmeticError> {
+                    self.checked_mul(other).ok_or(ArithmeticError::Overflow)
+                }
+
+                fn try_div(self, other: Self) -> Result<Self, ArithmeticError> {
+                    if other == 0 {
+                        return Err(ArithmeticError::DivisionByZero);
+                    }
+
+                    self.checked_div(other).ok_or(ArithmeticError::Overflow)
+                }
+
+                fn try_rem(self, other: Self) -> Result<Self, ArithmeticError> {
+                    if other == 0 {
+                        return Err(ArithmeticError::DivisionByZero);
+                    }
+
+                    self.checked_rem(other).ok_or(ArithmeticError::Overflow)
+                }
+
+                fn try_neg(self) -> Result<Self, ArithmeticError> {
+                    self.checked_neg().ok_or(ArithmeticError::Overflow)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i32, i64, i128, u32, u64);
+
+#[cfg(test)]
+mod tests {
+    use super::{ArithmeticError, Integer};
+
+    #[test]
+    fn test_checked_operations() {
+        assert_eq!(2i32.try_add(3), Ok(5));
+        assert_eq!(i32::MAX.try_add(1), Err(ArithmeticError::Overflow));
+        assert_eq!(0u32.try_sub(1), Err(ArithmeticError::Overflow));
+        assert_eq!(i64::MAX.try_mul(2), Err(ArithmeticError::Overflow));
+        assert_eq!(7i64.try_div(0), Err(ArithmeticError::DivisionByZero));
+        assert_eq!(7i64.try_rem(0), Err(ArithmeticError::DivisionByZero));
+        assert_eq!(i64::MIN.try_rem(-1), Err(ArithmeticError::Overflow));
+        assert_eq!(i128::MIN.try_neg(), Err(ArithmeticError::Overflow));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(i32::from_usize(42), Some(42));
+        assert_eq!(i32::from_usize(usize::MAX), None);
+        assert_eq!((-1i64).to_usize(), None);
+        assert_eq!(12u64.to_usize(), Some(12));
+        assert_eq!(u64::MAX.to_i128(), 18_446_744_073_709_551_615);
+    }
+}
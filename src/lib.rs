@@ -1,27 +1,52 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+
+#[cfg(feature = "std")]
 use std::time::{SystemTime, UNIX_EPOCH};
 
-mod day1;
-mod day10;
-mod day11;
-mod day12;
-mod day13;
-mod day14;
-mod day15;
-mod day16;
-mod day17;
-mod day18;
-mod day19;
-mod day2;
-mod day20;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
-mod day8;
-mod day9;
+mod collections;
+pub mod day1;
+pub mod day10;
+pub mod day11;
+pub mod day12;
+pub mod day13;
+pub mod day14;
+pub mod day15;
+pub mod day16;
+pub mod day17;
+pub mod day18;
+pub mod day19;
+pub mod day2;
+pub mod day20;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
+pub mod day7;
+pub mod day8;
+pub mod day9;
+#[cfg(feature = "std")]
+pub mod fuzz;
 mod grid;
-
+mod integer;
+mod prelude;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+pub mod repl;
+#[cfg(feature = "std")]
+pub mod server;
+#[cfg(all(test, feature = "std"))]
+mod snapshot;
+
+#[cfg(feature = "std")]
 fn time<F>(closure: F)
 where
     F: Fn(),
@@ -37,7 +62,7 @@ where
     );
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use std::fs::File;
@@ -53,11 +78,15 @@ mod tests {
 
     #[test]
     fn solve_day1() {
-        use day1::solve;
+        use day1::{all_distances, solve};
         let input = load_file("day1.txt");
 
-        assert_eq!(solve(&input, 1), 1343);
-        assert_eq!(solve(&input, input.len() / 2), 1274);
+        assert_eq!(solve(&input, 1), Ok(1343));
+        assert_eq!(solve(&input, input.len() / 2), Ok(1274));
+
+        let sums = all_distances(input.trim().as_bytes()).unwrap();
+        assert_eq!(sums[1], 1343);
+        assert_eq!(sums[input.trim().len() / 2], 1274);
     }
 
     #[test]
@@ -65,8 +94,8 @@ mod tests {
         use day2::{row_data_evenly_divisible, row_data_min_max, solve};
         let input = load_file("day2.txt");
 
-        assert_eq!(solve(&input, &row_data_min_max), 53460);
-        assert_eq!(solve(&input, &row_data_evenly_divisible), 282);
+        assert_eq!(solve(&input, &row_data_min_max), Ok(53460));
+        assert_eq!(solve(&input, &row_data_evenly_divisible), Ok(282));
     }
 
     #[test]
@@ -74,7 +103,7 @@ mod tests {
         use day3::{solve, solve_star_two};
 
         assert_eq!(solve(312051), 430);
-        assert_eq!(solve_star_two(312051), 312453);
+        assert_eq!(solve_star_two(312051), Ok(312453));
     }
 
     #[test]
@@ -93,8 +122,24 @@ mod tests {
 
         let input = load_file("day5.txt");
 
-        assert_eq!(solve(&input, |_| 1), 376976);
-        assert_eq!(solve(&input, |i| if i >= 3 { -1 } else { 1 }), 29227751);
+        assert_eq!(solve(&input, |_| 1), Ok(376976));
+        assert_eq!(solve(&input, |i| if i >= 3 { -1 } else { 1 }), Ok(29227751));
+    }
+
+    #[test]
+    fn day5_run_fast_skips_jumps() {
+        use day5::JumpMaze;
+
+        let input = load_file("day5.txt");
+        let maze = || JumpMaze::parse(&input, |i: i32| if i >= 3 { -1 } else { 1 }).unwrap();
+
+        let mut slow = maze();
+        assert_eq!(slow.run(), Ok(29227751));
+        assert_eq!(slow.dispatches(), slow.steps());
+
+        let mut fast = maze();
+        assert_eq!(fast.run_fast(), Ok(29227751));
+        assert!(fast.dispatches() * 4 < fast.steps());
     }
 
     #[test]
@@ -103,7 +148,7 @@ mod tests {
 
         let input = load_file("day6.txt");
 
-        assert_eq!(solve(&input), (11137, 1037));
+        assert_eq!(solve(&input), Ok((11137, 1037)));
     }
 
     #[test]
@@ -112,7 +157,7 @@ mod tests {
 
         let input = load_file("day7.txt");
 
-        assert_eq!(solve(&input), ("qibuqqg".to_owned(), 1079));
+        assert_eq!(solve(&input), Ok(("qibuqqg".to_owned(), 1079)));
     }
 
     #[test]
@@ -121,7 +166,7 @@ mod tests {
 
         let input = load_file("day8.txt");
 
-        assert_eq!(solve(&input), (2971, 4254));
+        assert_eq!(solve::<i32>(&input), Ok((2971, 4254)));
     }
 
     #[test]
@@ -130,7 +175,7 @@ mod tests {
 
         let input = load_file("day9.txt");
 
-        assert_eq!(solve(&input), (17390, 7825));
+        assert_eq!(solve(&input), Ok((17390, 7825)));
     }
 
     #[test]
@@ -193,14 +238,15 @@ mod tests {
 
     #[test]
     fn solve_day16() {
-        use day16::solve;
+        use day16::{cycle_length, solve};
 
         let input = load_file("day16.txt");
 
-        assert_eq!(solve(&input, 16, 1), "fgmobeaijhdpkcln");
+        assert_eq!(solve(&input, 16, 1), Ok("fgmobeaijhdpkcln".to_owned()));
 
         // Cycles at 24 iteratiors. 1_000_000_000 % 24 == 16
-        assert_eq!(solve(&input, 16, 16), "lgmkacfjbopednhi");
+        assert_eq!(solve(&input, 16, 16), Ok("lgmkacfjbopednhi".to_owned()));
+        assert_eq!(cycle_length(&input, 16), Ok(24));
     }
 
     #[test]
@@ -216,8 +262,8 @@ mod tests {
         use day18::{solve, solve2};
         let input = load_file("day18.txt");
 
-        assert_eq!(solve(&input), 3188);
-        assert_eq!(solve2(&input), 7112);
+        assert_eq!(solve::<i64>(&input), Ok(3188));
+        assert_eq!(solve2::<i64>(&input), Ok(7112));
     }
 
     #[test]
@@ -225,7 +271,7 @@ mod tests {
         use day19::solve;
         let input = load_file("day19.txt");
 
-        assert_eq!(solve(&input), (String::from("MKXOIHZNBL"), 17872));
+        assert_eq!(solve(&input), Ok((String::from("MKXOIHZNBL"), 17872)));
     }
 
     #[test]
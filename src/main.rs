@@ -0,0 +1,159 @@
+extern crate advent_of_code;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+use advent_of_code::day7::{self, Format};
+use advent_of_code::{fuzz, repl, server};
+
+fn usage() -> ! {
+    eprintln!("Usage: aoc serve [--port <port>]");
+    eprintln!("       aoc fuzz [--iterations <count>] [--seed <seed>] [--target <day>]");
+    eprintln!("       aoc repl day8");
+    eprintln!("       aoc tower [--format <text|dot|json>] [--input <path>]");
+    process::exit(2);
+}
+
+fn serve(args: &[String]) {
+    let mut port = 8080;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--port" => {
+                port = args
+                    .next()
+                    .and_then(|value| value.parse::<u16>().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            _ => usage(),
+        }
+    }
+
+    if let Err(error) = server::serve(port) {
+        eprintln!("Server failed: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run_fuzz(args: &[String]) {
+    let mut iterations = 100_000;
+    let mut seed = 2017;
+    let mut target_name = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--iterations" => {
+                iterations = args
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            "--target" => target_name = Some(args.next().unwrap_or_else(|| usage()).clone()),
+            _ => usage(),
+        }
+    }
+
+    let targets = fuzz::targets()
+        .into_iter()
+        .filter(|target| {
+            target_name
+                .as_ref()
+                .map_or(true, |name| name == target.name)
+        })
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        eprintln!("Unknown fuzz target");
+        process::exit(2);
+    }
+
+    let report = fuzz::run(&targets, iterations, seed);
+    println!(
+        "{} executions, {} crashes",
+        report.executions,
+        report.crashes.len()
+    );
+
+    for crash in &report.crashes {
+        match crash.save(Path::new(fuzz::REGRESSIONS_DIR)) {
+            Ok(path) => println!(
+                "{}: {} (saved to {})",
+                crash.target,
+                crash.message,
+                path.display()
+            ),
+            Err(error) => println!(
+                "{}: {} (failed to save: {})",
+                crash.target, crash.message, error
+            ),
+        }
+    }
+
+    if !report.crashes.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn tower(args: &[String]) {
+    let mut format = Format::Text;
+    let mut path = "day7.txt".to_owned();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--format" => {
+                format = args
+                    .next()
+                    .and_then(|value| value.parse::<Format>().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            "--input" => path = args.next().unwrap_or_else(|| usage()).clone(),
+            _ => usage(),
+        }
+    }
+
+    let mut input = String::new();
+    if let Err(error) = File::open(&path).and_then(|mut f| f.read_to_string(&mut input)) {
+        eprintln!("Unable to read {}: {}", path, error);
+        process::exit(1);
+    }
+
+    let tower = day7::parse(&input).unwrap_or_else(|error| {
+        eprintln!("Invalid tower: {}", error);
+        process::exit(1);
+    });
+    let mut output = String::new();
+    day7::export(&tower, format, &mut output).expect("Writing to a String cannot fail");
+    print!("{}", output);
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(|command| command.as_ref()) {
+        Some("serve") => serve(&args[1..]),
+        Some("fuzz") => run_fuzz(&args[1..]),
+        Some("tower") => tower(&args[1..]),
+        Some("repl") => match args.get(1).map(|day| day.as_ref()) {
+            Some("day8") if args.len() == 2 => {
+                let stdin = io::stdin();
+                if let Err(error) = repl::run(stdin.lock(), io::stdout()) {
+                    eprintln!("REPL failed: {}", error);
+                    process::exit(1);
+                }
+            }
+            _ => usage(),
+        },
+        _ => usage(),
+    }
+}
@@ -0,0 +1,7 @@
+//! The parts of the std prelude that live in `alloc`, so solvers can be
+//! written the same way with and without the `std` feature.
+
+pub use alloc::borrow::ToOwned;
+pub use alloc::boxed::Box;
+pub use alloc::string::{String, ToString};
+pub use alloc::vec::Vec;
@@ -0,0 +1,304 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use day1;
+use day10;
+use day11;
+use day12;
+use day13;
+use day14;
+use day15;
+use day16;
+use day17;
+use day18;
+use day19;
+use day2;
+use day20;
+use day3;
+use day4;
+use day5;
+use day6;
+use day7;
+use day8;
+use day9;
+use integer::ArithmeticError;
+
+pub type Solver = fn(&str) -> Result<String, String>;
+pub type Visualizer = fn(&str) -> String;
+
+/// Where the puzzle input for a day comes from when none is supplied.
+pub enum Input {
+    File(&'static str),
+    Inline(&'static str),
+}
+
+impl Input {
+    pub fn load(&self) -> io::Result<String> {
+        match *self {
+            Input::File(path) => {
+                let mut input = String::new();
+                File::open(path)?.read_to_string(&mut input)?;
+
+                Ok(input)
+            }
+            Input::Inline(value) => Ok(value.to_owned()),
+        }
+    }
+}
+
+pub struct Day {
+    pub number: u32,
+    pub input: Input,
+    pub parts: Vec<Solver>,
+    pub visualizer: Option<Visualizer>,
+}
+
+impl Day {
+    fn new(number: u32, input: Input, parts: Vec<Solver>) -> Self {
+        Day {
+            number,
+            input,
+            parts,
+            visualizer: None,
+        }
+    }
+
+    fn with_visualizer(mut self, visualizer: Visualizer) -> Self {
+        self.visualizer = Some(visualizer);
+        self
+    }
+}
+
+fn parse_number(input: &str) -> Result<u32, String> {
+    input
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("Expected a single number, found {:?}", input.trim()))
+}
+
+fn parse_pair(input: &str) -> Result<(i64, i64), String> {
+    let values = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    if values.len() != 2 {
+        return Err(format!("Expected two numbers, found {}", values.len()));
+    }
+
+    Ok((values[0], values[1]))
+}
+
+fn day2_checksum(
+    input: &str,
+    data_for_row: &dyn Fn(&[i32]) -> Result<i32, ArithmeticError>,
+) -> Result<String, String> {
+    day2::solve(input, data_for_row).map(|total| total.to_string())
+}
+
+fn day16_star_two(input: &str) -> Result<String, String> {
+    let cycle = day16::cycle_length(input, 16)?;
+
+    day16::solve(input, 16, 1_000_000_000 % cycle)
+}
+
+pub fn days() -> Vec<Day> {
+    vec![
+        Day::new(
+            1,
+            Input::File("day1.txt"),
+            vec![
+                |input| day1::solve(input, 1).map(|sum| sum.to_string()),
+                |input| day1::solve(input, input.trim().len() / 2).map(|sum| sum.to_string()),
+            ],
+        ),
+        Day::new(
+            2,
+            Input::File("day2.txt"),
+            vec![
+                |input| day2_checksum(input, &day2::row_data_min_max),
+                |input| day2_checksum(input, &day2::row_data_evenly_divisible),
+            ],
+        ),
+        Day::new(
+            3,
+            Input::Inline("312051"),
+            vec![
+                |input| parse_number(input).map(|n| day3::solve(u64::from(n)).to_string()),
+                |input| {
+                    parse_number(input)
+                        .and_then(day3::solve_star_two)
+                        .map(|value| value.to_string())
+                },
+            ],
+        ),
+        Day::new(
+            4,
+            Input::File("day4.txt"),
+            vec![
+                |input| Ok(day4::solve::<day4::UniquenessValidator>(input).to_string()),
+                |input| Ok(day4::solve::<day4::AnagramValidator>(input).to_string()),
+            ],
+        )
+        .with_visualizer(|input| day4::report(input, &day4::AnagramValidator {}).to_string()),
+        Day::new(
+            5,
+            Input::File("day5.txt"),
+            vec![
+                |input| {
+                    day5::solve(input, |_| 1)
+                        .map(|steps| steps.to_string())
+                        .map_err(|error| error.to_string())
+                },
+                |input| {
+                    day5::solve(input, |i| if i >= 3 { -1 } else { 1 })
+                        .map(|steps| steps.to_string())
+                        .map_err(|error| error.to_string())
+                },
+            ],
+        ),
+        Day::new(
+            6,
+            Input::File("day6.txt"),
+            vec![
+                |input| day6::solve(input).map(|(cycles, _)| cycles.to_string()),
+                |input| day6::solve(input).map(|(_, loop_size)| loop_size.to_string()),
+            ],
+        ),
+        Day::new(
+            7,
+            Input::File("day7.txt"),
+            vec![
+                |input| {
+                    day7::solve(input)
+                        .map(|(name, _)| name)
+                        .map_err(|e| e.to_string())
+                },
+                |input| {
+                    day7::solve(input)
+                        .map(|(_, weight)| weight.to_string())
+                        .map_err(|e| e.to_string())
+                },
+            ],
+        )
+        .with_visualizer(day7::visualize),
+        Day::new(
+            8,
+            Input::File("day8.txt"),
+            vec![
+                |input| {
+                    day8::solve::<i64>(input)
+                        .map(|(largest, _)| largest.to_string())
+                        .map_err(|e| e.to_string())
+                },
+                |input| {
+                    day8::solve::<i64>(input)
+                        .map(|(_, largest_observed)| largest_observed.to_string())
+                        .map_err(|e| e.to_string())
+                },
+            ],
+        ),
+        Day::new(
+            9,
+            Input::File("day9.txt"),
+            vec![
+                |input| {
+                    day9::solve(input)
+                        .map(|(score, _)| score.to_string())
+                        .map_err(|e| e.to_string())
+                },
+                |input| {
+                    day9::solve(input)
+                        .map(|(_, garbage)| garbage.to_string())
+                        .map_err(|e| e.to_string())
+                },
+            ],
+        ),
+        Day::new(
+            10,
+            Input::Inline("106,16,254,226,55,2,1,166,177,247,93,0,255,228,60,36"),
+            vec![|input| Ok(day10::solve(input, 256).to_string()), |input| {
+                Ok(day10::solve2(input, 256, 64))
+            }],
+        ),
+        Day::new(
+            11,
+            Input::File("day11.txt"),
+            vec![|input| Ok(day11::solve(input).to_string()), |input| {
+                Ok(day11::solve2(input).to_string())
+            }],
+        ),
+        Day::new(
+            12,
+            Input::File("day12.txt"),
+            vec![|input| Ok(day12::solve(input).to_string()), |input| {
+                Ok(day12::solve2(input).to_string())
+            }],
+        ),
+        Day::new(
+            13,
+            Input::File("day13.txt"),
+            vec![|input| Ok(day13::solve(input).to_string()), |input| {
+                Ok(day13::solve2(input).to_string())
+            }],
+        ),
+        Day::new(
+            14,
+            Input::Inline("hwlqcszp"),
+            vec![|input| Ok(day14::solve(input).to_string()), |input| {
+                Ok(day14::solve2(input).to_string())
+            }],
+        )
+        .with_visualizer(day14::visualize),
+        Day::new(
+            15,
+            Input::Inline("783, 325"),
+            vec![
+                |input| parse_pair(input).map(|(a, b)| day15::solve(a, b).to_string()),
+                |input| parse_pair(input).map(|(a, b)| day15::solve2(a, b).to_string()),
+            ],
+        ),
+        Day::new(
+            16,
+            Input::File("day16.txt"),
+            vec![|input| day16::solve(input, 16, 1), day16_star_two],
+        ),
+        Day::new(
+            17,
+            Input::Inline("304"),
+            vec![
+                |input| parse_number(input).map(|n| day17::solve(n as usize).to_string()),
+                |input| parse_number(input).map(|n| day17::solve2(n as usize).to_string()),
+            ],
+        ),
+        Day::new(
+            18,
+            Input::File("day18.txt"),
+            vec![
+                |input| day18::solve::<i64>(input).map(|frequency| frequency.to_string()),
+                |input| day18::solve2::<i64>(input).map(|sent| sent.to_string()),
+            ],
+        ),
+        Day::new(
+            19,
+            Input::File("day19.txt"),
+            vec![
+                |input| day19::solve(input).map(|(letters, _)| letters),
+                |input| day19::solve(input).map(|(_, steps)| steps.to_string()),
+            ],
+        ),
+        Day::new(
+            20,
+            Input::File("day20.txt"),
+            vec![|input| Ok(day20::star_one(input).to_string()), |input| {
+                Ok(day20::star_two(input).to_string())
+            }],
+        ),
+    ]
+}
+
+pub fn find(number: u32) -> Option<Day> {
+    days().into_iter().find(|day| day.number == number)
+}
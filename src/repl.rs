@@ -0,0 +1,264 @@
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+use day8::{self, Expression, History};
+
+const HELP: &str = "\
+<register> <operation> <operand> if <condition>  run an instruction
+:get <register>                                 show a register
+:registers                                      show every register, sorted
+:load <path>                                    run every instruction in a file
+:undo                                           take back the last instruction
+:help                                           show this help
+:quit                                           leave";
+
+/// A day8 register machine driven one line at a time. Lines that fail to
+/// parse or to run are reported and leave the registers untouched.
+pub struct Session {
+    history: History<i64>,
+    program: Vec<Expression<i64>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            history: History::new(),
+            program: Vec::new(),
+        }
+    }
+
+    fn execute(&mut self, expression: Expression<i64>) -> Result<bool, String> {
+        let fired = self
+            .history
+            .evaluate(&expression)
+            .map_err(|error| error.to_string())?;
+        self.program.push(expression);
+
+        Ok(fired)
+    }
+
+    fn run(&mut self, expression: Expression<i64>) -> Result<String, String> {
+        if !self.execute(expression)? {
+            return Ok("skipped".to_owned());
+        }
+
+        let write = &self.history.writes()[self.history.writes().len() - 1];
+        Ok(format!(
+            "{}: {} -> {}",
+            write.register, write.old, write.new
+        ))
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let mut input = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut input))
+            .map_err(|error| format!("Unable to read {}: {}", path, error))?;
+        let program = day8::parse::<i64>(&input).map_err(|error| error.to_string())?;
+
+        let count = program.len();
+        let mut fired = 0;
+        for (i, expression) in program.into_iter().enumerate() {
+            match self.execute(expression) {
+                Ok(true) => fired += 1,
+                Ok(false) => {}
+                Err(error) => {
+                    for _ in 0..i {
+                        self.history.undo();
+                        self.program.pop();
+                    }
+                    return Err(format!(
+                        "{} at instruction {} of {}, nothing was loaded",
+                        error,
+                        i + 1,
+                        count
+                    ));
+                }
+            }
+        }
+
+        Ok(format!("ran {} instructions, {} fired", count, fired))
+    }
+
+    fn undo(&mut self) -> Result<String, String> {
+        let expression = self.program.pop().ok_or("Nothing to undo")?;
+
+        Ok(match self.history.undo() {
+            Some(write) => format!(
+                "undid {} ({}: {} -> {})",
+                expression, write.register, write.new, write.old
+            ),
+            None => format!("undid {}", expression),
+        })
+    }
+
+    fn table(&self) -> String {
+        let registers = self.history.registers().sorted();
+        let width = registers
+            .iter()
+            .map(|&(register, _)| register.len())
+            .max()
+            .unwrap_or(0);
+
+        registers
+            .iter()
+            .map(|&(register, value)| format!("{:<width$} {}", register, value, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Handles one line of input, `None` means the session is over.
+    pub fn handle(&mut self, line: &str) -> Option<Result<String, String>> {
+        let line = line.trim();
+        let mut words = line.split_whitespace();
+
+        Some(match words.next() {
+            None => Ok(String::new()),
+            Some(":quit") => return None,
+            Some(":help") => Ok(HELP.to_owned()),
+            Some(":registers") => Ok(self.table()),
+            Some(":undo") => self.undo(),
+            Some(":get") => match (words.next(), words.next()) {
+                (Some(register), None) => Ok(self.history.registers().get(register).to_string()),
+                _ => Err("Usage: :get <register>".to_owned()),
+            },
+            Some(":load") => match line[":load".len()..].trim() {
+                "" => Err("Usage: :load <path>".to_owned()),
+                path => self.load(path),
+            },
+            Some(command) if command.starts_with(':') => {
+                Err(format!("Unknown command {}, try :help", command))
+            }
+            Some(_) => Expression::parse(line).and_then(|expression| self.run(expression)),
+        })
+    }
+}
+
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut session = Session::new();
+    let mut lines = input.lines();
+
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return writeln!(output),
+        };
+        match session.handle(&line) {
+            None => return Ok(()),
+            Some(Ok(ref result)) if result.is_empty() => {}
+            Some(Ok(result)) => writeln!(output, "{}", result)?,
+            Some(Err(error)) => writeln!(output, "error: {}", error)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A file path unique to this process and call, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+
+            TempFile(std::env::temp_dir().join(format!("{}-{}-{}", name, process::id(), count)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn transcript(input: &str) -> String {
+        let mut output = Vec::new();
+        run(input.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let output = transcript(
+            "b inc 5 if a > 1
+a inc 1 if b < 5
+c dec -10 if a >= 1
+c pow 2 if a > 0
+c div 0 if a > 0
+:get c
+:undo
+:registers
+:undo
+:undo
+:undo
+:frobnicate
+:quit
+a inc 1 if a == 0
+",
+        );
+
+        assert_eq!(
+            output,
+            "> skipped
+> a: 0 -> 1
+> c: 0 -> 10
+> error: Unknown operation \"pow\"
+> error: Division by zero
+> 10
+> undid c dec -10 if a >= 1 (c: 10 -> 0)
+> a 1
+> undid a inc 1 if b < 5 (a: 1 -> 0)
+> undid b inc 5 if a > 1
+> error: Nothing to undo
+> error: Unknown command :frobnicate, try :help
+> "
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let mut session = Session::new();
+
+        assert_eq!(
+            session.handle(":load day8.txt"),
+            Some(Ok("ran 1000 instructions, 814 fired".to_owned()))
+        );
+        assert_eq!(session.history.registers().max_register_value(), 2971);
+        assert!(session
+            .handle(":load does-not-exist.txt")
+            .unwrap()
+            .unwrap_err()
+            .starts_with("Unable to read does-not-exist.txt"));
+
+        let file = TempFile::new("aoc-repl-load");
+        std::fs::write(&file.0, "x inc 1 if x == 0\nx div 0 if x == 1\n").unwrap();
+        let mut session = Session::new();
+        session.handle("a inc 1 if a == 0");
+        let result = session.handle(&format!(":load {}", file.0.display()));
+
+        assert_eq!(
+            result,
+            Some(Err(
+                "Division by zero at instruction 2 of 2, nothing was loaded".to_owned()
+            ))
+        );
+        assert_eq!(session.history.registers().sorted(), vec![("a", 1)]);
+        assert_eq!(session.program.len(), 1);
+    }
+}
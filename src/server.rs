@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use registry::{self, Day};
+
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// How long a connection may stall reading the request or writing the
+/// response before it is dropped.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn read(stream: &mut TcpStream) -> Result<Request, String> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader
+            .read_line(&mut request_line)
+            .map_err(|e| e.to_string())?;
+
+        let parts = request_line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(format!("Malformed request line {:?}", request_line));
+        }
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).map_err(|e| e.to_string())?;
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            let mut split = header.splitn(2, ':');
+            let name = split.next().unwrap_or("").trim().to_lowercase();
+            let value = split.next().unwrap_or("").trim();
+            if name == "content-length" {
+                content_length = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid Content-Length {}", value))?;
+            }
+        }
+
+        if content_length > MAX_BODY_SIZE {
+            return Err(format!(
+                "Request body of {} bytes is too large",
+                content_length
+            ));
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+        Ok(Request {
+            method: parts[0].to_owned(),
+            path: parts[1].to_owned(),
+            body,
+        })
+    }
+}
+
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response {
+            status: "200 OK",
+            body,
+        }
+    }
+
+    fn not_found() -> Self {
+        Response {
+            status: "404 Not Found",
+            body: page("Not found", "<p>Nothing here.</p>"),
+        }
+    }
+
+    fn bad_request(message: &str) -> Self {
+        Response {
+            status: "400 Bad Request",
+            body: page("Bad request", &format!("<p>{}</p>", escape(message))),
+        }
+    }
+
+    fn write(&self, stream: &mut TcpStream) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+fn escape(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_owned(),
+            '<' => "&lt;".to_owned(),
+            '>' => "&gt;".to_owned(),
+            '"' => "&quot;".to_owned(),
+            '\'' => "&#39;".to_owned(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+fn url_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = String::from_utf8_lossy(&bytes[i + 1..i + 3]).into_owned();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_form(body: &[u8]) -> HashMap<String, String> {
+    String::from_utf8_lossy(body)
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            let key = url_decode(split.next().unwrap_or(""));
+            let value = url_decode(split.next().unwrap_or(""));
+
+            (key, value)
+        })
+        .collect()
+}
+
+fn page(title: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{0}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}
+textarea {{ width: 100%; height: 12em; font-family: monospace; }}
+pre {{ background: #f4f4f4; padding: 1em; overflow: auto; }}
+.answer {{ font-size: 2em; font-family: monospace; }}
+.error {{ color: #b00; }}
+</style>
+</head>
+<body>
+<p><a href=\"/\">All days</a></p>
+<h1>{0}</h1>
+{1}
+</body>
+</html>
+",
+        escape(title),
+        content
+    )
+}
+
+fn index() -> Response {
+    let items = registry::days()
+        .iter()
+        .map(|day| {
+            format!(
+                "<li><a href=\"/day/{0}\">Day {0}</a> ({1} parts{2})</li>",
+                day.number,
+                day.parts.len(),
+                if day.visualizer.is_some() {
+                    ", visualization"
+                } else {
+                    ""
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Response::ok(page(
+        "Advent of Code 2017",
+        &format!("<ul>\n{}\n</ul>", items),
+    ))
+}
+
+fn form(day: &Day, input: &str, selected_part: usize) -> String {
+    let parts = (0..day.parts.len())
+        .map(|i| {
+            format!(
+                "<option value=\"{0}\"{1}>Part {2}</option>",
+                i,
+                if i == selected_part { " selected" } else { "" },
+                i + 1
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    format!(
+        "<form method=\"post\" action=\"/day/{0}\">
+<p><label>Input source <select name=\"source\" id=\"source\">
+<option value=\"bundled\">Bundled input</option>
+<option value=\"custom\">Custom input below</option>
+</select></label>
+<input type=\"file\" id=\"upload\"></p>
+<textarea name=\"input\" id=\"input\">{1}</textarea>
+<p><label>Part <select name=\"part\">{2}</select></label>
+<button type=\"submit\">Run</button></p>
+</form>
+<script>
+document.getElementById('upload').addEventListener('change', function (event) {{
+  var reader = new FileReader();
+  reader.onload = function () {{
+    document.getElementById('input').value = reader.result;
+    document.getElementById('source').value = 'custom';
+  }};
+  reader.readAsText(event.target.files[0]);
+}});
+document.getElementById('input').addEventListener('input', function () {{
+  document.getElementById('source').value = 'custom';
+}});
+</script>",
+        day.number,
+        escape(input),
+        parts
+    )
+}
+
+fn run_guarded<F, T>(closure: F) -> Result<T, String>
+where
+    F: FnOnce() -> T,
+{
+    panic::catch_unwind(AssertUnwindSafe(closure)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            format!("Solver panicked: {}", message)
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            format!("Solver panicked: {}", message)
+        } else {
+            "Solver panicked".to_owned()
+        }
+    })
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{}s and {}ns", duration.as_secs(), duration.subsec_nanos())
+}
+
+fn show_day(day: &Day) -> Response {
+    let input = day.input.load().unwrap_or_default();
+    let title = format!("Day {}", day.number);
+
+    Response::ok(page(&title, &form(day, &input, 0)))
+}
+
+fn run_day(day: &Day, body: &[u8]) -> Response {
+    let fields = parse_form(body);
+    let part = match fields.get("part").map(|part| part.parse::<usize>()) {
+        Some(Ok(part)) if part < day.parts.len() => part,
+        _ => return Response::bad_request("Unknown part"),
+    };
+
+    let input = match fields.get("source").map(|source| source.as_ref()) {
+        Some("custom") => fields.get("input").cloned().unwrap_or_default(),
+        _ => match day.input.load() {
+            Ok(input) => input,
+            Err(error) => {
+                return Response::bad_request(&format!("Unable to load bundled input: {}", error))
+            }
+        },
+    };
+
+    let solver = day.parts[part];
+    let start = Instant::now();
+    let answer = run_guarded(|| solver(&input)).and_then(|result| result);
+    let elapsed = start.elapsed();
+
+    let mut content = match answer {
+        Ok(answer) => format!("<p class=\"answer\">{}</p>", escape(&answer)),
+        Err(error) => format!("<p class=\"error\">{}</p>", escape(&error)),
+    };
+    content.push_str(&format!(
+        "<p>Part {} took {}</p>",
+        part + 1,
+        format_duration(elapsed)
+    ));
+
+    if let Some(visualizer) = day.visualizer {
+        match run_guarded(|| visualizer(&input)) {
+            Ok(rendered) => content.push_str(&format!("<pre>{}</pre>", escape(&rendered))),
+            Err(error) => content.push_str(&format!("<p class=\"error\">{}</p>", escape(&error))),
+        }
+    }
+
+    content.push_str(&form(day, &input, part));
+    let title = format!("Day {}", day.number);
+
+    Response::ok(page(&title, &content))
+}
+
+fn route(request: &Request) -> Response {
+    let segments = request
+        .path
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    match (request.method.as_ref(), segments.as_slice()) {
+        ("GET", []) => index(),
+        (method, ["day", number]) => {
+            let day = match number.parse::<u32>().ok().and_then(registry::find) {
+                Some(day) => day,
+                None => return Response::not_found(),
+            };
+
+            match method {
+                "GET" => show_day(&day),
+                "POST" => run_day(&day, &request.body),
+                _ => Response::not_found(),
+            }
+        }
+        _ => Response::not_found(),
+    }
+}
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let response = match Request::read(&mut stream) {
+        Ok(request) => route(&request),
+        Err(error) => Response::bad_request(&error),
+    };
+
+    response.write(&mut stream)
+}
+
+pub fn serve(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Serving on http://127.0.0.1:{}/", port);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(error) = handle(stream) {
+                        eprintln!("Failed to handle request: {}", error);
+                    }
+                });
+            }
+            Err(error) => eprintln!("Failed to accept connection: {}", error),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_url_decode() {
+        assert_eq!(url_decode("a+b%2Cc"), "a b,c");
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(url_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_parse_form() {
+        let form = parse_form(b"part=1&input=0+2+7+0&source=custom");
+
+        assert_eq!(form["part"], "1");
+        assert_eq!(form["input"], "0 2 7 0");
+        assert_eq!(form["source"], "custom");
+    }
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route(&request("GET", "/", "")).status, "200 OK");
+        assert_eq!(
+            route(&request("GET", "/day/99", "")).status,
+            "404 Not Found"
+        );
+
+        let response = route(&request(
+            "POST",
+            "/day/6",
+            "part=0&source=custom&input=0+2+7+0",
+        ));
+        assert_eq!(response.status, "200 OK");
+        assert!(response.body.contains("<p class=\"answer\">5</p>"));
+
+        let response = route(&request("POST", "/day/6", "part=7&source=custom"));
+        assert_eq!(response.status, "400 Bad Request");
+    }
+
+    #[test]
+    fn test_solver_panic_is_reported() {
+        assert_eq!(
+            run_guarded(|| -> u32 { panic!("Invalid memory banks") }),
+            Err("Solver panicked: Invalid memory banks".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_solver_parse_error_is_reported() {
+        let response = route(&request(
+            "POST",
+            "/day/6",
+            "part=0&source=custom&input=not+numbers",
+        ));
+
+        assert!(response
+            .body
+            .contains("<p class=\"error\">Expected only numbers found &quot;not&quot;</p>"));
+    }
+
+    #[test]
+    fn test_solver_error_is_reported() {
+        let response = route(&request(
+            "POST",
+            "/day/8",
+            "part=0&source=custom&input=a+pow+1+if+b+%3E+0",
+        ));
+
+        assert!(response
+            .body
+            .contains("<p class=\"error\">Line 1: Unknown operation &quot;pow&quot;</p>"));
+    }
+}
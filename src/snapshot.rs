@@ -0,0 +1,124 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+const UPDATE_VARIABLE: &str = "UPDATE_SNAPSHOTS";
+/// Above this many cell comparisons the diff only lists mismatching lines
+/// instead of computing a minimal diff.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(SNAPSHOTS_DIR).join(format!("{}.snap", name))
+}
+
+fn should_update() -> bool {
+    env::var(UPDATE_VARIABLE)
+        .map(|value| value != "0" && !value.is_empty())
+        .unwrap_or(false)
+}
+
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    let (n, m) = (expected.len(), actual.len());
+
+    if n * m > MAX_DIFF_CELLS {
+        return (0..n.max(m))
+            .filter(|&i| expected.get(i) != actual.get(i))
+            .flat_map(|i| {
+                vec![
+                    expected
+                        .get(i)
+                        .map(|line| format!("{:>5} - {}", i + 1, line)),
+                    actual.get(i).map(|line| format!("{:>5} + {}", i + 1, line)),
+                ]
+            })
+            .filter_map(|line| line)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    // Longest common subsequence of lines, lengths[i][j] covers expected[i..]
+    // and actual[j..].
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            output.push(format!("{:>5} - {}", i + 1, expected[i]));
+            i += 1;
+        } else {
+            output.push(format!("{:>5} + {}", j + 1, actual[j]));
+            j += 1;
+        }
+    }
+
+    output.join("\n")
+}
+
+/// Compares `actual` with the snapshot stored in `snapshots/<name>.snap`.
+/// Run the tests with `UPDATE_SNAPSHOTS=1` to create or re-bless snapshots.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = path(name);
+    let mut contents = actual.to_owned();
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+
+    if should_update() {
+        fs::create_dir_all(SNAPSHOTS_DIR).expect("Unable to create snapshots directory");
+        File::create(&path)
+            .and_then(|mut f| f.write_all(contents.as_bytes()))
+            .expect("Unable to write snapshot");
+        return;
+    }
+
+    let mut expected = String::new();
+    if File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut expected))
+        .is_err()
+    {
+        panic!(
+            "Missing snapshot {}, run the tests with {}=1 to create it",
+            path.display(),
+            UPDATE_VARIABLE
+        );
+    }
+
+    if expected != contents {
+        panic!(
+            "Snapshot {} does not match, run the tests with {}=1 to update it\n{}",
+            path.display(),
+            UPDATE_VARIABLE,
+            diff(&expected, &contents)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\nc", "a\nb\nc"), "");
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), "    2 - b\n    2 + x");
+        assert_eq!(diff("a\nc", "a\nb\nc"), "    2 + b");
+        assert_eq!(diff("a\nb", "a"), "    2 - b");
+    }
+}